@@ -0,0 +1,421 @@
+//! Binary cache of parsed tables and their indexes
+//!
+//! Every number is little endian and every string is prefixed with its byte length.
+
+use crate::columnar::ColumnarData;
+use crate::constraint::Constraint;
+use crate::index::{ColumnIndex, HashIndex, OrderKey, OrderedIndex, UniqueKey};
+use crate::table::{Storage, Table, TableSource};
+use crate::{CIndexError, CIndexResult};
+use dcsv::{Value, ValueType};
+use std::collections::{BTreeMap, HashMap};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const MAGIC: &[u8; 6] = b"CINDEX";
+const VERSION: u32 = 4;
+
+/// Write tables into a cache stream
+pub(crate) fn write_tables(
+    out: &mut impl Write,
+    tables: &HashMap<String, Table>,
+) -> CIndexResult<()> {
+    let mut writer = CacheWriter { out };
+    writer.out.write_all(MAGIC)?;
+    writer.u32(VERSION)?;
+    writer.u64(tables.len() as u64)?;
+    for (name, table) in tables {
+        writer.str(name)?;
+        writer.table(table)?;
+    }
+    Ok(())
+}
+
+/// Read tables from a cache stream
+///
+/// Tables from a source file are read from the file again if the file has changed.
+pub(crate) fn read_tables(input: &mut impl Read) -> CIndexResult<Vec<(String, Table)>> {
+    let mut reader = CacheReader { input };
+    let mut magic = [0; 6];
+    reader.input.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(CIndexError::InvalidCache(
+            "Given file is not a cindex cache".to_owned(),
+        ));
+    }
+    let version = reader.u32()?;
+    if version != VERSION {
+        return Err(CIndexError::InvalidCache(format!(
+            "Unsupported cache version {}",
+            version
+        )));
+    }
+
+    let count = reader.u64()?;
+    let mut tables = vec![];
+    for _ in 0..count {
+        let name = reader.str()?;
+        tables.push((name, reader.table()?));
+    }
+    Ok(tables)
+}
+
+struct CacheWriter<'a, W: Write> {
+    out: &'a mut W,
+}
+
+impl<'a, W: Write> CacheWriter<'a, W> {
+    fn u8(&mut self, value: u8) -> CIndexResult<()> {
+        Ok(self.out.write_all(&[value])?)
+    }
+
+    fn u32(&mut self, value: u32) -> CIndexResult<()> {
+        Ok(self.out.write_all(&value.to_le_bytes())?)
+    }
+
+    fn u64(&mut self, value: u64) -> CIndexResult<()> {
+        Ok(self.out.write_all(&value.to_le_bytes())?)
+    }
+
+    fn str(&mut self, value: &str) -> CIndexResult<()> {
+        self.u64(value.len() as u64)?;
+        Ok(self.out.write_all(value.as_bytes())?)
+    }
+
+    fn value(&mut self, value: &Value) -> CIndexResult<()> {
+        match value {
+            Value::Text(text) => {
+                self.u8(0)?;
+                self.str(text)
+            }
+            Value::Number(num) => {
+                self.u8(1)?;
+                Ok(self.out.write_all(&(*num as i64).to_le_bytes())?)
+            }
+        }
+    }
+
+    fn positions(&mut self, positions: &[usize]) -> CIndexResult<()> {
+        self.u64(positions.len() as u64)?;
+        for position in positions {
+            self.u64(*position as u64)?;
+        }
+        Ok(())
+    }
+
+    fn table(&mut self, table: &Table) -> CIndexResult<()> {
+        match &table.source {
+            Some(source) => {
+                self.u8(1)?;
+                self.str(&source.path.to_string_lossy())?;
+                self.u64(source.len)?;
+                self.u64(source.modified.0)?;
+                self.u32(source.modified.1)?;
+            }
+            None => self.u8(0)?,
+        }
+
+        match &table.storage {
+            Storage::Memory(data) => {
+                self.u8(0)?;
+                self.data(data)?;
+            }
+            // Mapped table is mapped from its source again
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(data) => {
+                self.u8(1)?;
+                self.u64(data.len() as u64)?;
+            }
+        }
+        self.indexes(table)?;
+        self.constraints(&table.constraints)?;
+        self.primary_key(table.primary_key.as_ref())
+    }
+
+    /// Write columns of a primary key while its map is built again on read
+    fn primary_key(&mut self, key: Option<&UniqueKey>) -> CIndexResult<()> {
+        match key {
+            Some(key) => {
+                self.u8(1)?;
+                self.u64(key.columns.len() as u64)?;
+                for column in &key.columns {
+                    self.str(column)?;
+                }
+            }
+            None => self.u8(0)?,
+        }
+        Ok(())
+    }
+
+    /// Write constraints where predicates of a check are written as a raw statement
+    fn constraints(&mut self, constraints: &[Constraint]) -> CIndexResult<()> {
+        self.u64(constraints.len() as u64)?;
+        for constraint in constraints {
+            match constraint {
+                Constraint::NotNull(column) => {
+                    self.u8(0)?;
+                    self.str(column)?;
+                }
+                Constraint::Unique(columns) => {
+                    self.u8(1)?;
+                    self.u64(columns.len() as u64)?;
+                    for column in columns {
+                        self.str(column)?;
+                    }
+                }
+                Constraint::Check(predicates) => {
+                    self.u8(2)?;
+                    let predicates = predicates
+                        .iter()
+                        .map(|p| p.to_string())
+                        .collect::<Vec<_>>()
+                        .join(" AND ");
+                    self.str(&predicates)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Write columns and rows of an in memory table
+    fn data(&mut self, data: &ColumnarData) -> CIndexResult<()> {
+        self.u64(data.columns.len() as u64)?;
+        for column in &data.columns {
+            self.str(&column.name)?;
+            self.u8(match column.column_type {
+                ValueType::Text => 0,
+                ValueType::Number => 1,
+            })?;
+        }
+
+        self.u64(data.len() as u64)?;
+        for row in 0..data.len() {
+            for column in &data.columns {
+                self.value(column.get(row))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn indexes(&mut self, table: &Table) -> CIndexResult<()> {
+        self.u64(table.indexes.len() as u64)?;
+        for (column, index) in &table.indexes {
+            self.str(column)?;
+            match index.as_ref() {
+                ColumnIndex::Hash(index) => {
+                    self.u8(0)?;
+                    self.u64(index.map.len() as u64)?;
+                    for (key, positions) in &index.map {
+                        self.str(key)?;
+                        self.positions(positions)?;
+                    }
+                }
+                ColumnIndex::Ordered(index) => {
+                    self.u8(1)?;
+                    self.u64(index.map.len() as u64)?;
+                    for (key, positions) in &index.map {
+                        self.value(&key.0)?;
+                        self.positions(positions)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+struct CacheReader<'a, R: Read> {
+    input: &'a mut R,
+}
+
+impl<'a, R: Read> CacheReader<'a, R> {
+    fn u8(&mut self) -> CIndexResult<u8> {
+        let mut buf = [0; 1];
+        self.input.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn u32(&mut self) -> CIndexResult<u32> {
+        let mut buf = [0; 4];
+        self.input.read_exact(&mut buf)?;
+        Ok(u32::from_le_bytes(buf))
+    }
+
+    fn u64(&mut self) -> CIndexResult<u64> {
+        let mut buf = [0; 8];
+        self.input.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn str(&mut self) -> CIndexResult<String> {
+        let len = self.u64()? as usize;
+        let mut buf = vec![];
+        self.input.take(len as u64).read_to_end(&mut buf)?;
+        if buf.len() != len {
+            return Err(CIndexError::InvalidCache(
+                "Unexpected end of cache".to_owned(),
+            ));
+        }
+        String::from_utf8(buf)
+            .map_err(|_| CIndexError::InvalidCache("Cache has invalid utf8 string".to_owned()))
+    }
+
+    fn value(&mut self) -> CIndexResult<Value> {
+        match self.u8()? {
+            0 => Ok(Value::Text(self.str()?)),
+            1 => Ok(Value::Number(self.u64()? as i64 as isize)),
+            tag => Err(invalid_tag("value", tag)),
+        }
+    }
+
+    fn positions(&mut self, row_count: usize) -> CIndexResult<Vec<usize>> {
+        let len = self.u64()?;
+        let mut positions = vec![];
+        for _ in 0..len {
+            let position = self.u64()? as usize;
+            if position >= row_count {
+                return Err(CIndexError::InvalidCache(format!(
+                    "Index has out of range row {}",
+                    position
+                )));
+            }
+            positions.push(position);
+        }
+        Ok(positions)
+    }
+
+    fn table(&mut self) -> CIndexResult<Table> {
+        let source = match self.u8()? {
+            0 => None,
+            1 => Some(TableSource {
+                path: PathBuf::from(self.str()?),
+                len: self.u64()?,
+                modified: (self.u64()?, self.u32()?),
+            }),
+            tag => return Err(invalid_tag("source", tag)),
+        };
+
+        let (mut table, row_count) = match self.u8()? {
+            0 => self.data()?,
+            1 => {
+                let row_count = self.u64()? as usize;
+                (Self::mapped(source.as_ref())?, row_count)
+            }
+            tag => return Err(invalid_tag("storage", tag)),
+        };
+        table.indexes = self.indexes(row_count)?;
+        table.set_constraints(self.constraints()?)?;
+        match self.u8()? {
+            0 => (),
+            1 => {
+                let column_count = self.u64()?;
+                let columns = (0..column_count)
+                    .map(|_| self.str())
+                    .collect::<CIndexResult<Vec<_>>>()?;
+                table.primary_key.replace(UniqueKey::new(columns));
+                table.build_key()?;
+            }
+            tag => return Err(invalid_tag("primary key", tag)),
+        }
+        table.source = source;
+        Ok(table)
+    }
+
+    #[cfg(feature = "mmap")]
+    fn mapped(source: Option<&TableSource>) -> CIndexResult<Table> {
+        let source = source.ok_or_else(|| {
+            CIndexError::InvalidCache("Mapped table doesn't have a source file".to_owned())
+        })?;
+        Table::from_mapped(&source.path)
+    }
+
+    #[cfg(not(feature = "mmap"))]
+    fn mapped(_: Option<&TableSource>) -> CIndexResult<Table> {
+        Err(CIndexError::InvalidCache(
+            "Mapped table requires \"mmap\" feature".to_owned(),
+        ))
+    }
+
+    /// Read columns and rows of an in memory table
+    fn data(&mut self) -> CIndexResult<(Table, usize)> {
+        let column_count = self.u64()? as usize;
+        let mut columns = vec![];
+        for _ in 0..column_count {
+            let name = self.str()?;
+            let column_type = match self.u8()? {
+                0 => ValueType::Text,
+                1 => ValueType::Number,
+                tag => return Err(invalid_tag("column type", tag)),
+            };
+            columns.push((name, column_type));
+        }
+
+        let mut data = ColumnarData::new(columns);
+        let row_count = self.u64()? as usize;
+        for _ in 0..row_count {
+            let values = (0..column_count)
+                .map(|_| self.value())
+                .collect::<CIndexResult<Vec<_>>>()?;
+            data.push_row(values);
+        }
+        Ok((Table::from_data(data), row_count))
+    }
+
+    /// Read indexes of a table which has given number of rows
+    fn indexes(&mut self, row_count: usize) -> CIndexResult<HashMap<String, Arc<ColumnIndex>>> {
+        let mut indexes = HashMap::new();
+        let index_count = self.u64()?;
+        for _ in 0..index_count {
+            let column = self.str()?;
+            let index_type = self.u8()?;
+            let entry_count = self.u64()?;
+            let index = match index_type {
+                0 => {
+                    let mut map = HashMap::new();
+                    for _ in 0..entry_count {
+                        map.insert(self.str()?, self.positions(row_count)?);
+                    }
+                    ColumnIndex::Hash(HashIndex { map })
+                }
+                1 => {
+                    let mut map = BTreeMap::new();
+                    for _ in 0..entry_count {
+                        map.insert(OrderKey(self.value()?), self.positions(row_count)?);
+                    }
+                    ColumnIndex::Ordered(OrderedIndex { map })
+                }
+                tag => return Err(invalid_tag("index", tag)),
+            };
+            indexes.insert(column, Arc::new(index));
+        }
+        Ok(indexes)
+    }
+
+    fn constraints(&mut self) -> CIndexResult<Vec<Constraint>> {
+        let count = self.u64()?;
+        let mut constraints = vec![];
+        for _ in 0..count {
+            let constraint = match self.u8()? {
+                0 => Constraint::NotNull(self.str()?),
+                1 => {
+                    let column_count = self.u64()?;
+                    Constraint::Unique(
+                        (0..column_count)
+                            .map(|_| self.str())
+                            .collect::<CIndexResult<Vec<_>>>()?,
+                    )
+                }
+                2 => Constraint::check(&self.str()?)?,
+                tag => return Err(invalid_tag("constraint", tag)),
+            };
+            constraints.push(constraint);
+        }
+        Ok(constraints)
+    }
+}
+
+/// Error of an unknown tag of given kind
+fn invalid_tag(kind: &str, tag: u8) -> CIndexError {
+    CIndexError::InvalidCache(format!("Invalid {} tag {}", kind, tag))
+}
@@ -0,0 +1,299 @@
+//! Columnar storage of an in memory table
+//!
+//! Values of a column are stored together in order of rows. Text values are interned so that a
+//! repeated text is stored only once per column.
+
+use crate::stats::ColumnStats;
+use dcsv::{Value, ValueType, VirtualData};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+#[cfg(not(feature = "rayon"))]
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+#[derive(Clone)]
+pub(crate) struct ColumnarData {
+    pub(crate) columns: Vec<ColumnData>,
+    row_count: usize,
+}
+
+/// Values of a single column
+#[derive(Clone)]
+pub(crate) struct ColumnData {
+    pub(crate) name: String,
+    pub(crate) column_type: ValueType,
+    pub(crate) cells: Cells,
+}
+
+#[derive(Clone)]
+pub(crate) enum Cells {
+    Plain(Vec<Value>),
+    Interned {
+        // Distinct values in order of appearance
+        dictionary: Vec<Value>,
+        // Dictionary ids keyed by hashes of their texts so that a text is stored only once.
+        // Colliding text takes the next free key.
+        lookup: HashMap<u64, u32>,
+        // Dictionary id of every row
+        ids: Vec<u32>,
+    },
+}
+
+impl ColumnarData {
+    /// Create empty storage with names and types of columns
+    pub fn new(columns: Vec<(String, ValueType)>) -> Self {
+        Self {
+            columns: columns
+                .into_iter()
+                .map(|(name, column_type)| ColumnData::new(name, column_type))
+                .collect(),
+            row_count: 0,
+        }
+    }
+
+    /// Convert row based data into columns
+    ///
+    /// Rows are consumed one by one so that both layouts are not fully held at the same time.
+    pub fn from_virtual(mut data: VirtualData) -> Self {
+        let mut columnar = Self::new(
+            data.columns
+                .iter()
+                .map(|c| (c.name.clone(), c.column_type))
+                .collect(),
+        );
+        let names = data
+            .columns
+            .iter()
+            .map(|c| c.name.clone())
+            .collect::<Vec<_>>();
+        for mut row in data.rows.drain(..) {
+            columnar.push_row(
+                names
+                    .iter()
+                    .map(|name| row.values.remove(name).unwrap_or_default())
+                    .collect(),
+            );
+        }
+        columnar
+    }
+
+    /// Get the number of rows
+    pub fn len(&self) -> usize {
+        self.row_count
+    }
+
+    /// Get a position of column
+    pub fn column_position(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c.name == name)
+    }
+
+    /// Get a value of a cell
+    pub fn value(&self, column: usize, row: usize) -> &Value {
+        self.columns[column].get(row)
+    }
+
+    /// Append a row whose values are in order of columns
+    pub fn push_row(&mut self, values: Vec<Value>) {
+        for (column, value) in self.columns.iter_mut().zip(values) {
+            column.push(value);
+        }
+        self.row_count += 1;
+    }
+
+    /// Replace values of a column at given rows
+    pub fn set_values(&mut self, column: usize, values: Vec<(usize, Value)>) {
+        self.columns[column].set_values(values);
+    }
+
+    /// Remove rows whose flag is true
+    pub fn remove_rows(&mut self, removed: &[bool]) {
+        for column in self.columns.iter_mut() {
+            column.remove_rows(removed);
+        }
+        self.row_count -= removed.iter().filter(|removed| **removed).count();
+    }
+
+    /// Append a column whose every row has a default value
+    pub fn add_column(&mut self, name: String, column_type: ValueType, default: Value) {
+        let mut column = ColumnData::new(name, column_type);
+        for _ in 0..self.row_count {
+            column.push(default.clone());
+        }
+        self.columns.push(column);
+    }
+
+    /// Remove a column at a position
+    pub fn remove_column(&mut self, column: usize) {
+        self.columns.remove(column);
+    }
+}
+
+impl ColumnData {
+    fn new(name: String, column_type: ValueType) -> Self {
+        let cells = match column_type {
+            ValueType::Text => Cells::Interned {
+                dictionary: vec![],
+                lookup: HashMap::new(),
+                ids: vec![],
+            },
+            ValueType::Number => Cells::Plain(vec![]),
+        };
+        Self {
+            name,
+            column_type,
+            cells,
+        }
+    }
+
+    pub fn get(&self, row: usize) -> &Value {
+        match &self.cells {
+            Cells::Plain(values) => &values[row],
+            Cells::Interned {
+                dictionary, ids, ..
+            } => &dictionary[ids[row] as usize],
+        }
+    }
+
+    /// Collect statistics of the column
+    ///
+    /// Interned column only reads its dictionary.
+    pub fn stats(&self) -> ColumnStats {
+        match &self.cells {
+            #[cfg(feature = "rayon")]
+            Cells::Plain(values) => {
+                // Distinct values of chunks are merged
+                let distinct = values
+                    .par_iter()
+                    .fold(HashMap::new, |mut distinct, value| {
+                        distinct.entry(value.to_string()).or_insert(value);
+                        distinct
+                    })
+                    .reduce(HashMap::new, |mut a, b| {
+                        for (key, value) in b {
+                            a.entry(key).or_insert(value);
+                        }
+                        a
+                    });
+                ColumnStats::from_distinct(distinct.into_values())
+            }
+            #[cfg(not(feature = "rayon"))]
+            Cells::Plain(values) => {
+                let mut seen = HashSet::new();
+                ColumnStats::from_distinct(values.iter().filter(|v| seen.insert(v.to_string())))
+            }
+            Cells::Interned { dictionary, .. } => ColumnStats::from_distinct(dictionary.iter()),
+        }
+    }
+
+    /// Replace values at given rows
+    ///
+    /// Dictionary entries which are no longer used are removed afterwards.
+    fn set_values(&mut self, values: Vec<(usize, Value)>) {
+        match &mut self.cells {
+            Cells::Plain(cells) => {
+                for (row, value) in values {
+                    cells[row] = value;
+                }
+            }
+            Cells::Interned {
+                dictionary,
+                lookup,
+                ids,
+            } => {
+                for (row, value) in values {
+                    ids[row] = intern(dictionary, lookup, value);
+                }
+                compact(dictionary, lookup, ids);
+            }
+        }
+    }
+
+    /// Remove rows whose flag is true
+    fn remove_rows(&mut self, removed: &[bool]) {
+        let mut flags = removed.iter();
+        match &mut self.cells {
+            Cells::Plain(values) => values.retain(|_| !flags.next().unwrap_or(&false)),
+            Cells::Interned {
+                dictionary,
+                lookup,
+                ids,
+            } => {
+                ids.retain(|_| !flags.next().unwrap_or(&false));
+                compact(dictionary, lookup, ids);
+            }
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        match &mut self.cells {
+            Cells::Plain(values) => values.push(value),
+            Cells::Interned {
+                dictionary,
+                lookup,
+                ids,
+            } => ids.push(intern(dictionary, lookup, value)),
+        }
+    }
+}
+
+/// Get a dictionary id of a value, adding the value if it is new
+fn intern(dictionary: &mut Vec<Value>, lookup: &mut HashMap<u64, u32>, value: Value) -> u32 {
+    let mut key = text_hash(&value);
+    while let Some(id) = lookup.get(&key) {
+        if same_text(&dictionary[*id as usize], &value) {
+            return *id;
+        }
+        key = key.wrapping_add(1);
+    }
+    let id = dictionary.len() as u32;
+    dictionary.push(value);
+    lookup.insert(key, id);
+    id
+}
+
+/// Hash a value's text which keys the lookup of a dictionary
+fn text_hash(value: &Value) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match value {
+        Value::Text(text) => text.hash(&mut hasher),
+        other => other.to_string().hash(&mut hasher),
+    }
+    hasher.finish()
+}
+
+/// Check if values have a same text
+fn same_text(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Text(a), Value::Text(b)) => a == b,
+        _ => a.to_string() == b.to_string(),
+    }
+}
+
+/// Remove dictionary entries which no row refers to
+fn compact(dictionary: &mut Vec<Value>, lookup: &mut HashMap<u64, u32>, ids: &mut [u32]) {
+    let mut used = vec![false; dictionary.len()];
+    for id in ids.iter() {
+        used[*id as usize] = true;
+    }
+    if used.iter().all(|used| *used) {
+        return;
+    }
+    // New id of every kept entry
+    let mut remap = vec![0; dictionary.len()];
+    let mut kept = vec![];
+    for (id, value) in std::mem::take(dictionary).into_iter().enumerate() {
+        if used[id] {
+            remap[id] = kept.len() as u32;
+            kept.push(value);
+        }
+    }
+    lookup.clear();
+    for value in kept {
+        intern(dictionary, lookup, value);
+    }
+    for id in ids.iter_mut() {
+        *id = remap[*id as usize];
+    }
+}
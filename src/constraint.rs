@@ -0,0 +1,120 @@
+//! Constraints which every row of a table should satisfy
+
+use crate::parser::{quote, Parser};
+use crate::query::Predicate;
+use crate::{CIndexError, CIndexResult};
+use std::fmt::Display;
+
+/// Rule which every row of a table should satisfy
+#[derive(Debug, Clone)]
+pub enum Constraint {
+    /// Value of a column should not be empty
+    NotNull(String),
+    /// Values of columns should be unique across rows
+    ///
+    /// Row whose every key column is empty is never a duplicate, use NotNull to reject it.
+    Unique(Vec<String>),
+    /// Row should qualify every predicate
+    ///
+    /// Empty value always satisfies a predicate, use NotNull to reject it.
+    Check(Vec<Predicate>),
+}
+
+impl Constraint {
+    /// Create a constraint which rejects an empty value of a column
+    pub fn not_null(column: &str) -> Self {
+        Self::NotNull(column.to_owned())
+    }
+
+    /// Create a constraint which rejects a duplicate of values of columns
+    pub fn unique(columns: Vec<impl AsRef<str>>) -> Self {
+        Self::Unique(columns.iter().map(|c| c.as_ref().to_owned()).collect())
+    }
+
+    /// Create a constraint from predicates such as "age >= 0 AND status IN (open, closed)"
+    pub fn check(predicates: &str) -> CIndexResult<Self> {
+        let query = Parser::new().parse(&format!("SELECT * FROM table WHERE {}", predicates))?;
+        if query.has_placeholders() {
+            return Err(CIndexError::InvalidQueryStatement(
+                "Check constraint cannot have placeholders".to_owned(),
+            ));
+        }
+        match query.predicates {
+            Some(predicates) if !predicates.is_empty() => Ok(Self::Check(predicates)),
+            _ => Err(CIndexError::InvalidQueryStatement(format!(
+                "Check constraint requires predicates but given \"{}\"",
+                predicates
+            ))),
+        }
+    }
+
+    /// Get columns which the constraint refers to
+    pub fn columns(&self) -> Vec<&str> {
+        match self {
+            Self::NotNull(column) => vec![column],
+            Self::Unique(columns) => columns.iter().map(|c| c.as_str()).collect(),
+            Self::Check(predicates) => predicates.iter().map(|p| p.column.as_str()).collect(),
+        }
+    }
+
+    /// Rename a column which the constraint refers to
+    pub(crate) fn rename_column(&mut self, from: &str, to: &str) {
+        match self {
+            Self::NotNull(column) => {
+                if column == from {
+                    *column = to.to_owned();
+                }
+            }
+            Self::Unique(columns) => {
+                for column in columns.iter_mut().filter(|c| *c == from) {
+                    *column = to.to_owned();
+                }
+            }
+            Self::Check(predicates) => {
+                for predicate in predicates.iter_mut().filter(|p| p.column == from) {
+                    predicate.set_column(to);
+                }
+            }
+        }
+    }
+}
+
+impl Display for Constraint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotNull(column) => write!(f, "NOT NULL ({})", quote(column)),
+            Self::Unique(columns) => write!(
+                f,
+                "UNIQUE ({})",
+                columns
+                    .iter()
+                    .map(|c| quote(c))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::Check(predicates) => write!(
+                f,
+                "CHECK ({})",
+                predicates
+                    .iter()
+                    .map(|p| p.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" AND ")
+            ),
+        }
+    }
+}
+
+/// Row which doesn't satisfy a constraint
+#[derive(Debug, Clone)]
+pub struct Violation {
+    /// Position of the row in its table, starting from zero
+    pub row: usize,
+    pub constraint: Constraint,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Row {} violates {}", self.row, self.constraint)
+    }
+}
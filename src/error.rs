@@ -1,3 +1,5 @@
+use crate::constraint::Violation;
+
 pub type CIndexResult<T> = Result<T, CIndexError>;
 
 #[derive(Debug)]
@@ -9,6 +11,10 @@ pub enum CIndexError {
     InvalidColumn(String),
     InvalidDataType(String),
     InvalidQueryStatement(String),
+    InvalidCache(String),
+    InvalidThreadPool(String),
+    InvalidTransaction(String),
+    ConstraintViolation(Vec<Violation>),
 }
 
 impl std::fmt::Display for CIndexError {
@@ -23,6 +29,16 @@ impl std::fmt::Display for CIndexError {
             Self::InvalidColumn(err) => write!(f, "Invalid column\n= {}", err),
             Self::InvalidDataType(err) => write!(f, "Invalid data type \n= {}", err),
             Self::InvalidQueryStatement(err) => write!(f, "Invalid query statement \n= {}", err),
+            Self::InvalidCache(err) => write!(f, "Invalid cache \n= {}", err),
+            Self::InvalidThreadPool(err) => write!(f, "Invalid thread pool \n= {}", err),
+            Self::InvalidTransaction(err) => write!(f, "Invalid transaction \n= {}", err),
+            Self::ConstraintViolation(violations) => {
+                write!(f, "Constraint violation")?;
+                for violation in violations {
+                    write!(f, "\n= {}", violation)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -0,0 +1,49 @@
+//! Undo and redo journal of a table
+
+use crate::table::Table;
+use std::sync::Arc;
+
+/// Snapshots of a table before and after modifications
+///
+/// Snapshots are shared so that copying a journal for a transaction doesn't copy tables.
+#[derive(Clone, Default)]
+pub(crate) struct History {
+    undo: Vec<Arc<Table>>,
+    redo: Vec<Arc<Table>>,
+}
+
+impl History {
+    /// Record a table before a modification
+    ///
+    /// Redo is cleared and the oldest snapshot is dropped over the limit.
+    pub fn record(&mut self, table: Table, limit: usize) {
+        self.undo.push(Arc::new(table));
+        let excess = self.undo.len().saturating_sub(limit);
+        self.undo.drain(..excess);
+        self.redo.clear();
+    }
+
+    /// Replace a current table with the latest snapshot to undo
+    ///
+    /// This returns false if there is nothing to undo.
+    pub fn undo(&mut self, current: &mut Table) -> bool {
+        swap(&mut self.undo, &mut self.redo, current)
+    }
+
+    /// Replace a current table with the latest snapshot to redo
+    ///
+    /// This returns false if there is nothing to redo.
+    pub fn redo(&mut self, current: &mut Table) -> bool {
+        swap(&mut self.redo, &mut self.undo, current)
+    }
+}
+
+/// Replace a current table with a snapshot popped from source and push it to target
+fn swap(source: &mut Vec<Arc<Table>>, target: &mut Vec<Arc<Table>>, current: &mut Table) -> bool {
+    let snapshot = match source.pop() {
+        Some(snapshot) => Arc::try_unwrap(snapshot).unwrap_or_else(|shared| (*shared).clone()),
+        None => return false,
+    };
+    target.push(Arc::new(std::mem::replace(current, snapshot)));
+    true
+}
@@ -0,0 +1,211 @@
+use crate::query::Operator;
+use dcsv::Value;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::sync::Arc;
+
+/// Type of a column index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexType {
+    /// Serves "=" and "IN" predicates
+    Hash,
+    /// Serves range predicates and ordering besides what hash index serves
+    Ordered,
+}
+
+/// Index of a column
+#[derive(Debug, Clone)]
+pub(crate) enum ColumnIndex {
+    Hash(HashIndex),
+    Ordered(OrderedIndex),
+}
+
+impl ColumnIndex {
+    /// Build an index from values of a column in order of rows
+    pub fn build(values: impl Iterator<Item = Value>, index_type: IndexType) -> Self {
+        match index_type {
+            IndexType::Hash => Self::Hash(HashIndex::build(values)),
+            IndexType::Ordered => Self::Ordered(OrderedIndex::build(values)),
+        }
+    }
+
+    pub fn index_type(&self) -> IndexType {
+        match self {
+            Self::Hash(_) => IndexType::Hash,
+            Self::Ordered(_) => IndexType::Ordered,
+        }
+    }
+
+    /// Add a value of a new row
+    ///
+    /// Position should be bigger than every position in the index.
+    pub fn insert(&mut self, value: &Value, position: usize) {
+        match self {
+            Self::Hash(index) => index
+                .map
+                .entry(value.to_string())
+                .or_default()
+                .push(position),
+            Self::Ordered(index) => index
+                .map
+                .entry(OrderKey(value.clone()))
+                .or_default()
+                .push(position),
+        }
+    }
+
+    /// Check if index can serve an operator
+    pub fn serves(&self, operator: Operator) -> bool {
+        match operator {
+            Operator::Equal | Operator::In => true,
+            Operator::Bigger
+            | Operator::BiggerOrEqual
+            | Operator::Smaller
+            | Operator::SmallerOrEqual
+            | Operator::Between => self.index_type() == IndexType::Ordered,
+            Operator::NotEqual | Operator::Like => false,
+        }
+    }
+
+    /// Get sorted row positions which qualify an operator with typed arguments
+    ///
+    /// Operator should be the one that index serves.
+    pub fn lookup(&self, operator: Operator, arguments: &[Value]) -> Vec<usize> {
+        let mut positions = match self {
+            Self::Hash(index) => index.get_any(arguments),
+            Self::Ordered(index) => index.range(operator, arguments),
+        };
+        positions.sort_unstable();
+        positions.dedup();
+        positions
+    }
+}
+
+/// Hash index of a column which maps a cell value to row positions
+///
+/// Row positions are stored in ascending order.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HashIndex {
+    pub(crate) map: HashMap<String, Vec<usize>>,
+}
+
+impl HashIndex {
+    pub fn build(values: impl Iterator<Item = Value>) -> Self {
+        let mut map: HashMap<String, Vec<usize>> = HashMap::new();
+        for (position, value) in values.enumerate() {
+            map.entry(value.to_string()).or_default().push(position);
+        }
+        Self { map }
+    }
+
+    /// Get row positions which have any of given values
+    fn get_any(&self, values: &[Value]) -> Vec<usize> {
+        values
+            .iter()
+            .filter_map(|v| self.map.get(&v.to_string()))
+            .flatten()
+            .copied()
+            .collect()
+    }
+}
+
+/// Unique values of columns which map to a row position
+///
+/// Primary key and every unique constraint of a table keep one, which is updated along with
+/// rows. Map is shared between snapshots of a table until one of them is modified.
+#[derive(Debug, Clone)]
+pub(crate) struct UniqueKey {
+    pub(crate) columns: Vec<String>,
+    pub(crate) map: Arc<HashMap<Vec<String>, usize>>,
+}
+
+impl UniqueKey {
+    /// Create a key of columns whose map is built later
+    pub fn new(columns: Vec<String>) -> Self {
+        Self {
+            columns,
+            map: Arc::new(HashMap::new()),
+        }
+    }
+}
+
+/// Value which is totally ordered with column's typed comparison
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OrderKey(pub Value);
+
+impl PartialOrd for OrderKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OrderKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Values of a same column are always comparable
+        self.0.partial_cmp(&other.0).unwrap_or(Ordering::Equal)
+    }
+}
+
+/// Sorted index of a column which maps a cell value to row positions
+///
+/// Row positions of a same value are stored in ascending order.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OrderedIndex {
+    pub(crate) map: BTreeMap<OrderKey, Vec<usize>>,
+}
+
+impl OrderedIndex {
+    pub fn build(values: impl Iterator<Item = Value>) -> Self {
+        let mut map: BTreeMap<OrderKey, Vec<usize>> = BTreeMap::new();
+        for (position, value) in values.enumerate() {
+            map.entry(OrderKey(value)).or_default().push(position);
+        }
+        Self { map }
+    }
+
+    /// Get row positions in order of values
+    ///
+    /// Rows of a same value keep ascending order even if descending is true.
+    pub fn positions(&self, descending: bool) -> Vec<usize> {
+        if descending {
+            self.map.values().rev().flatten().copied().collect()
+        } else {
+            self.map.values().flatten().copied().collect()
+        }
+    }
+
+    /// Get row positions within a range of an operator
+    fn range(&self, operator: Operator, arguments: &[Value]) -> Vec<usize> {
+        let key = |index: usize| OrderKey(arguments[index].clone());
+        let bounds = match operator {
+            Operator::Bigger => (Bound::Excluded(key(0)), Bound::Unbounded),
+            Operator::BiggerOrEqual => (Bound::Included(key(0)), Bound::Unbounded),
+            Operator::Smaller => (Bound::Unbounded, Bound::Excluded(key(0))),
+            Operator::SmallerOrEqual => (Bound::Unbounded, Bound::Included(key(0))),
+            Operator::Between => (Bound::Included(key(0)), Bound::Included(key(1))),
+            Operator::Equal | Operator::In => {
+                return arguments
+                    .iter()
+                    .filter_map(|v| self.map.get(&OrderKey(v.clone())))
+                    .flatten()
+                    .copied()
+                    .collect();
+            }
+            Operator::NotEqual | Operator::Like => unreachable!("Not served by ordered index"),
+        };
+
+        // BTreeMap panics with a reversed range
+        if let (Bound::Included(min), Bound::Included(max)) = &bounds {
+            if min > max {
+                return vec![];
+            }
+        }
+
+        self.map
+            .range(bounds)
+            .flat_map(|(_, v)| v)
+            .copied()
+            .collect()
+    }
+}
@@ -1,20 +1,42 @@
-use crate::models::ColumnVariant;
-use crate::query::{Query, QueryFlagType};
-use crate::table::Table;
+use crate::cache;
+use crate::columnar::ColumnarData;
+use crate::constraint::Constraint;
+use crate::history::History;
+use crate::index::{IndexType, UniqueKey};
+use crate::models::{ColumnVariant, OrderType};
+use crate::parser::Parser;
+use crate::plan::QueryPlan;
+use crate::query::{Params, Predicate, PreparedQuery, Query, QueryFlagType, ResolvedColumns};
+use crate::statement::{Alteration, Conflict, InsertSource, Statement};
+use crate::stream::RecordReader;
+use crate::table::{ResolvedPredicate, Table, TopRows};
+use crate::writer::{self, WriterOption};
 use crate::ReaderOption;
 use crate::{consts, CIndexError, CIndexResult};
-use dcsv::Row;
+use dcsv::{Row, Value, ValueType};
 #[cfg(feature = "rayon")]
 use rayon::prelude::*;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{BufRead, Write};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
+
+/// Table and its history before a transaction, none if either didn't exist
+type Preserved = (Option<Table>, Option<History>);
 
 /// Entry struct for indexing csv tables
 pub struct Indexer {
     pub(crate) tables: HashMap<String, Table>,
     use_unix_newline: bool,
+    #[cfg(feature = "rayon")]
+    thread_pool: Option<rayon::ThreadPool>,
+    // Tables and their histories before an ongoing transaction modified them
+    transaction: Option<HashMap<String, Preserved>>,
+    history: HashMap<String, History>,
+    // Zero disables undo history
+    history_limit: usize,
 }
 
 impl Default for Indexer {
@@ -29,6 +51,11 @@ impl Indexer {
         Self {
             use_unix_newline: false,
             tables: HashMap::new(),
+            #[cfg(feature = "rayon")]
+            thread_pool: None,
+            transaction: None,
+            history: HashMap::new(),
+            history_limit: 0,
         }
     }
 
@@ -37,6 +64,188 @@ impl Indexer {
         self.use_unix_newline = tv;
     }
 
+    /// Use a dedicated thread pool with given number of threads for parallel iteration
+    ///
+    /// Zero count uses rayon's global thread pool, which is the default.
+    #[cfg(feature = "rayon")]
+    pub fn set_thread_count(&mut self, count: usize) -> CIndexResult<()> {
+        self.thread_pool = if count == 0 {
+            None
+        } else {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(count)
+                .build()
+                .map_err(|err| CIndexError::InvalidThreadPool(err.to_string()))?;
+            Some(pool)
+        };
+        Ok(())
+    }
+
+    /// Run an operation inside indexer's thread pool
+    fn run<T: Send>(&self, operation: impl FnOnce() -> T + Send) -> T {
+        #[cfg(feature = "rayon")]
+        if let Some(pool) = &self.thread_pool {
+            return pool.install(operation);
+        }
+        operation()
+    }
+
+    /// Start a transaction
+    ///
+    /// Every table which is modified afterwards is copied once so that a rollback can restore
+    /// it. Transactions cannot be nested.
+    pub fn begin(&mut self) -> CIndexResult<()> {
+        if self.transaction.is_some() {
+            return Err(CIndexError::InvalidTransaction(
+                "Transaction has already begun".to_owned(),
+            ));
+        }
+        self.transaction.replace(HashMap::new());
+        Ok(())
+    }
+
+    /// Keep every modification of an ongoing transaction
+    pub fn commit(&mut self) -> CIndexResult<()> {
+        self.transaction.take().ok_or_else(|| {
+            CIndexError::InvalidTransaction("There is no transaction to commit".to_owned())
+        })?;
+        Ok(())
+    }
+
+    /// Restore tables as they were when an ongoing transaction began
+    pub fn rollback(&mut self) -> CIndexResult<()> {
+        let preserved = self.transaction.take().ok_or_else(|| {
+            CIndexError::InvalidTransaction("There is no transaction to roll back".to_owned())
+        })?;
+        for (name, (table, history)) in preserved {
+            match table {
+                Some(table) => self.tables.insert(name.clone(), table),
+                None => self.tables.remove(&name),
+            };
+            match history {
+                Some(history) => self.history.insert(name, history),
+                None => self.history.remove(&name),
+            };
+        }
+        Ok(())
+    }
+
+    /// Run an operation as a transaction
+    ///
+    /// Every modification is rolled back if the operation returns an error.
+    pub fn transaction<T>(
+        &mut self,
+        operation: impl FnOnce(&mut Self) -> CIndexResult<T>,
+    ) -> CIndexResult<T> {
+        self.begin()?;
+        match operation(self) {
+            Ok(result) => {
+                self.commit()?;
+                Ok(result)
+            }
+            Err(err) => {
+                self.rollback()?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Keep snapshots of each table to undo up to given count of modifications
+    ///
+    /// Zero count disables undo history, which is the default. Every modification of a table
+    /// except adding, dropping and renaming it is recorded.
+    ///
+    /// Snapshot shares rows, indexes and keys with its table, and a modification copies only
+    /// what it writes. Still every entry can hold a full copy of a table's rows once the table
+    /// is modified after it, so memory grows with the count times the size of a table.
+    pub fn set_history_limit(&mut self, count: usize) {
+        self.history_limit = count;
+        if count == 0 {
+            self.history.clear();
+        }
+    }
+
+    /// Undo the latest modification of a table
+    ///
+    /// This returns false if there is nothing to undo.
+    pub fn undo(&mut self, table_name: &str) -> CIndexResult<bool> {
+        self.restore(table_name, History::undo)
+    }
+
+    /// Redo the latest undone modification of a table
+    ///
+    /// This returns false if there is nothing to redo.
+    pub fn redo(&mut self, table_name: &str) -> CIndexResult<bool> {
+        self.restore(table_name, History::redo)
+    }
+
+    /// Replace a table with a snapshot of its history
+    fn restore(
+        &mut self,
+        table_name: &str,
+        swap: impl FnOnce(&mut History, &mut Table) -> bool,
+    ) -> CIndexResult<bool> {
+        self.get_table(table_name)?;
+        self.preserve(table_name);
+        match (
+            self.history.get_mut(table_name),
+            self.tables.get_mut(table_name),
+        ) {
+            (Some(history), Some(table)) => Ok(swap(history, table)),
+            _ => Ok(false),
+        }
+    }
+
+    /// Modify a table with an operation
+    ///
+    /// Table is preserved for a rollback and recorded into its history if the operation
+    /// succeeds and changes it. Operations check rows before they write them, so a table is
+    /// copied only to record its history. Copy shares rows, indexes and keys, which are copied
+    /// when they are written, so a failed or empty modification doesn't copy them.
+    fn modify_table<T: Modification>(
+        &mut self,
+        table_name: &str,
+        operation: impl FnOnce(&mut Table) -> CIndexResult<T>,
+    ) -> CIndexResult<T> {
+        self.preserve(table_name);
+        let limit = self.history_limit;
+        let table = self.tables.get_mut(table_name).ok_or_else(|| {
+            CIndexError::InvalidTableName(format!("Table \"{}\" doesn't exist", table_name))
+        })?;
+        let snapshot = if limit > 0 { Some(table.clone()) } else { None };
+        let result = operation(table)?;
+        if let Some(snapshot) = snapshot.filter(|_| result.changed()) {
+            self.history
+                .entry(table_name.to_owned())
+                .or_default()
+                .record(snapshot, limit);
+        }
+        Ok(result)
+    }
+
+    /// Copy a table before it is modified in a transaction
+    fn preserve(&mut self, table_name: &str) {
+        if let Some(preserved) = self.transaction.as_mut() {
+            if !preserved.contains_key(table_name) {
+                preserved.insert(
+                    table_name.to_owned(),
+                    (
+                        self.tables.get(table_name).cloned(),
+                        self.history.get(table_name).cloned(),
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Add a table after collecting its statistics
+    fn insert_table(&mut self, table_name: &str, mut table: Table) {
+        self.preserve(table_name);
+        self.history.remove(table_name);
+        self.run(|| table.collect_stats());
+        self.tables.insert(table_name.to_owned(), table);
+    }
+
     /// Return newline with unix newline option considered
     fn get_newline(&self) -> &str {
         if self.use_unix_newline {
@@ -53,24 +262,228 @@ impl Indexer {
 
     /// Drop table
     pub fn drop_table(&mut self, table_name: &str) {
+        self.preserve(table_name);
         self.tables.remove(table_name);
+        self.history.remove(table_name);
+    }
+
+    /// Rename table
+    pub fn rename_table(&mut self, table_name: &str, new_name: &str) -> CIndexResult<()> {
+        if self.tables.contains_key(new_name) {
+            return Err(CIndexError::InvalidTableName(format!(
+                "Table \"{}\" already exists",
+                new_name
+            )));
+        }
+        self.preserve(table_name);
+        self.preserve(new_name);
+        let table = self.tables.remove(table_name).ok_or_else(|| {
+            CIndexError::InvalidTableName(format!("Table \"{}\" doesn't exist", table_name))
+        })?;
+        self.tables.insert(new_name.to_owned(), table);
+        if let Some(history) = self.history.remove(table_name) {
+            self.history.insert(new_name.to_owned(), history);
+        }
+        Ok(())
     }
 
     /// Add table
     pub fn add_table(&mut self, table_name: &str, input: impl BufRead) -> CIndexResult<()> {
-        self.tables
-            .insert(table_name.to_owned(), Table::new(input)?);
+        self.insert_table(table_name, Table::new(input)?);
         Ok(())
     }
 
+    /// Add table whose rows should satisfy constraints
+    ///
+    /// Table is not added if any row violates constraints, and every violation is reported.
+    pub fn add_table_with_constraints(
+        &mut self,
+        table_name: &str,
+        input: impl BufRead,
+        constraints: Vec<Constraint>,
+    ) -> CIndexResult<()> {
+        let mut table = Table::new(input)?;
+        table.set_constraints(constraints)?;
+        self.insert_table(table_name, table);
+        Ok(())
+    }
+
+    /// Declare columns whose values identify a row of a table
+    ///
+    /// Values of key columns should be unique and not empty, which is checked on every later
+    /// modification. Empty columns remove the primary key. Key is not declared if any row
+    /// violates it, and every violation is reported.
+    pub fn set_primary_key(&mut self, table_name: &str, columns: &[&str]) -> CIndexResult<()> {
+        let columns = columns.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        if columns.is_empty() {
+            return self.modify_table(table_name, |t| {
+                t.primary_key = None;
+                Ok(())
+            });
+        }
+        self.modify_table(table_name, |t| {
+            let mut key = UniqueKey::new(columns);
+            key.map = Arc::new(t.key_map(&key.columns, true)?);
+            t.primary_key.replace(key);
+            Ok(())
+        })
+    }
+
+    /// Get a row by values of primary key columns
+    ///
+    /// Values are in order of key columns.
+    pub fn get_by_key(&self, table_name: &str, key: &[&str]) -> CIndexResult<Option<Row>> {
+        let table = self.get_table(table_name)?;
+        let position = match table.key_position(key)? {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+        let record = table.record(position);
+        let mut row = Row::new();
+        for (column, name) in table.column_names().into_iter().enumerate() {
+            row.values
+                .insert(name.to_owned(), record.get(column).clone());
+        }
+        Ok(Some(row))
+    }
+
+    /// Replace constraints of a table
+    ///
+    /// Every later modification of the table should satisfy constraints. Constraints are not
+    /// replaced if any row violates them, and every violation is reported.
+    pub fn set_constraints(
+        &mut self,
+        table_name: &str,
+        constraints: Vec<Constraint>,
+    ) -> CIndexResult<()> {
+        self.modify_table(table_name, |t| t.set_constraints(constraints))
+    }
+
     pub fn add_table_with_option(
         &mut self,
         table_name: &str,
         input: impl BufRead,
         reader_option: ReaderOption,
     ) -> CIndexResult<()> {
-        self.tables
-            .insert(table_name.to_owned(), Table::build(input, reader_option)?);
+        self.insert_table(table_name, Table::build(input, reader_option)?);
+        Ok(())
+    }
+
+    /// Add table from a file
+    ///
+    /// File's size and modified time are saved with the table so that a cached table can be
+    /// validated by load_cache.
+    pub fn add_table_from_path(
+        &mut self,
+        table_name: &str,
+        path: impl AsRef<Path>,
+    ) -> CIndexResult<()> {
+        self.insert_table(table_name, Table::from_path(path.as_ref())?);
+        Ok(())
+    }
+
+    /// Write current rows of a table as csv
+    ///
+    /// Edits of a table are included, therefore a table can be saved after it is modified.
+    pub fn save_table(
+        &self,
+        table_name: &str,
+        mut writer: impl Write,
+        option: &WriterOption,
+    ) -> CIndexResult<()> {
+        let table = self.get_table(table_name)?;
+        writer::write_table(table, &mut writer, option, self.get_newline())
+    }
+
+    /// Save every table and its indexes into a binary cache file
+    pub fn save_cache(&self, path: impl AsRef<Path>) -> CIndexResult<()> {
+        let mut file = BufWriter::new(File::create(path)?);
+        cache::write_tables(&mut file, &self.tables)?;
+        file.flush()?;
+        Ok(())
+    }
+
+    /// Load tables and their indexes from a binary cache file
+    ///
+    /// Table which was added from a file is read from the file again if the file's size or
+    /// modified time has changed since the cache was saved. Table which has been edited since it
+    /// was added is kept as cached. No table is added if any table fails to load.
+    pub fn load_cache(&mut self, path: impl AsRef<Path>) -> CIndexResult<()> {
+        let mut file = BufReader::new(File::open(path)?);
+        let tables = cache::read_tables(&mut file)?
+            .into_iter()
+            .map(|(name, table)| {
+                let table = match &table.source {
+                    Some(source) if source.is_stale() => table.reload()?,
+                    _ => table,
+                };
+                Ok((name, table))
+            })
+            .collect::<CIndexResult<Vec<_>>>()?;
+        for (name, table) in tables {
+            self.insert_table(&name, table);
+        }
+        Ok(())
+    }
+
+    /// Add table from a memory mapped file
+    ///
+    /// Only the header and offsets of rows are kept in memory, and cells are decoded while a
+    /// table is queried. Every column of a mapped table is text.
+    #[cfg(feature = "mmap")]
+    pub fn add_table_mapped(
+        &mut self,
+        table_name: &str,
+        path: impl AsRef<Path>,
+    ) -> CIndexResult<()> {
+        self.insert_table(table_name, Table::from_mapped(path.as_ref())?);
+        Ok(())
+    }
+
+    /// Store a result of a query as a new table
+    ///
+    /// Columns keep their types and header map of the query renames them. Supplement columns
+    /// are empty text columns and TP flag is ignored.
+    pub fn materialize(&mut self, table_name: &str, query: &Query) -> CIndexResult<()> {
+        if self.tables.contains_key(table_name) {
+            return Err(CIndexError::InvalidTableName(format!(
+                "Table \"{}\" already exists",
+                table_name
+            )));
+        }
+        let source = self.get_table(&query.table_name)?;
+        let columns = source.column_names();
+        let targets = target_columns(query, &columns)?;
+        let positions = target_positions(&targets, &columns);
+
+        let mut names = HashSet::new();
+        let mut schema = vec![];
+        for (name, position) in header_names(query, &targets)?.into_iter().zip(&positions) {
+            if !names.insert(name.clone()) {
+                return Err(CIndexError::InvalidColumn(format!(
+                    "Column \"{}\" is selected more than once",
+                    name
+                )));
+            }
+            let column_type = position
+                .map(|position| source.column_type(columns[position]))
+                .unwrap_or(ValueType::Text);
+            schema.push((name, column_type));
+        }
+
+        let mut data = ColumnarData::new(schema);
+        for record in self.run(|| source.query(query))? {
+            data.push_row(
+                positions
+                    .iter()
+                    .map(|position| match position {
+                        Some(position) => record.get(*position).clone(),
+                        None => Value::default(),
+                    })
+                    .collect(),
+            );
+        }
+        self.insert_table(table_name, Table::from_data(data));
         Ok(())
     }
 
@@ -82,145 +495,486 @@ impl Indexer {
         input: impl BufRead,
         headers: &[String],
     ) -> CIndexResult<()> {
-        self.tables.insert(
-            table_name.to_owned(),
-            Table::new_with_headers(input, headers)?,
-        );
+        self.insert_table(table_name, Table::new_with_headers(input, headers)?);
         Ok(())
     }
 
     //<INDEXING>
     /// Index with raq query
-    pub fn index_raw(&self, raw_query: &str, out_option: OutOption) -> CIndexResult<()> {
-        self.index(Query::from_str(raw_query)?, out_option)
+    ///
+    /// EXPLAIN statement writes a query plan instead of records.
+    pub fn index_raw(&self, raw_query: &str, mut out_option: OutOption) -> CIndexResult<()> {
+        match Parser::new().parse_statement(raw_query)? {
+            Statement::Select(query) => self.index(query, out_option),
+            Statement::Explain(query) => {
+                let plan = self.explain(&query)?;
+                self.write(&(plan.to_string() + self.get_newline()), &mut out_option)
+            }
+            _ => Err(CIndexError::InvalidQueryStatement(
+                "Statement modifies indexer and should be executed with execute_raw".to_owned(),
+            )),
+        }
     }
 
-    /// Index with pre-built query
-    pub fn index(&self, query: Query, mut out_option: OutOption) -> CIndexResult<()> {
-        let records = self.index_table(query)?;
+    /// Execute any raw statement including ones which modify indexer
+    ///
+    /// This returns the number of affected rows, which is zero for read only statements. UNDO
+    /// and REDO return one if a table is restored and zero if there is no history to restore.
+    pub fn execute_raw(
+        &mut self,
+        raw_statement: &str,
+        out_option: OutOption,
+    ) -> CIndexResult<usize> {
+        let affected = match Parser::new().parse_statement(raw_statement)? {
+            Statement::CreateIndex {
+                table,
+                column,
+                index_type,
+            } => {
+                self.create_index_with_type(&table, &column, index_type)?;
+                0
+            }
+            Statement::DropIndex { table, column } => {
+                self.drop_index(&table, &column)?;
+                0
+            }
+            Statement::Insert {
+                table,
+                columns,
+                source,
+                on_conflict,
+            } => {
+                let rows = match source {
+                    InsertSource::Values(rows) => rows,
+                    InsertSource::Select(query) => {
+                        let source = self.get_table(&query.table_name)?;
+                        self.run(|| self.select_values(source, &query))?
+                    }
+                };
+                match on_conflict {
+                    Some(conflict) => self.modify_table(&table, |t| {
+                        t.upsert_rows(&columns, rows, conflict == Conflict::Update)
+                    })?,
+                    None => self.modify_table(&table, |t| t.insert_rows(&columns, rows))?,
+                }
+            }
+            Statement::Update { assignments, query } => {
+                self.modify_table(&query.table_name, |t| t.update(&assignments, &query))?
+            }
+            Statement::Delete(query) => {
+                self.modify_table(&query.table_name, |t| t.delete(&query))?
+            }
+            Statement::Begin => {
+                self.begin()?;
+                0
+            }
+            Statement::Commit => {
+                self.commit()?;
+                0
+            }
+            Statement::Rollback => {
+                self.rollback()?;
+                0
+            }
+            Statement::Undo(table) => self.undo(&table)? as usize,
+            Statement::Redo(table) => self.redo(&table)? as usize,
+            Statement::CreateTable { table, query } => {
+                self.materialize(&table, &query)?;
+                0
+            }
+            Statement::AlterTable { table, alteration } => {
+                match alteration {
+                    Alteration::AddColumn { column, default } => {
+                        self.modify_table(&table, |t| t.add_column(&column, &default))?
+                    }
+                    Alteration::DropColumn(column) => {
+                        self.modify_table(&table, |t| t.drop_column(&column))?
+                    }
+                    Alteration::RenameColumn { from, to } => {
+                        self.modify_table(&table, |t| t.rename_column(&from, &to))?
+                    }
+                    Alteration::RenameTable(name) => self.rename_table(&table, &name)?,
+                }
+                0
+            }
+            _ => {
+                self.index_raw(raw_statement, out_option)?;
+                0
+            }
+        };
+        Ok(affected)
+    }
 
-        for row in records {
-            self.write(&(row.join(",") + self.get_newline()), &mut out_option)?;
-        }
-        Ok(())
+    /// Insert rows into a table
+    ///
+    /// Values of a row are in order of given columns, and every column is used if columns are
+    /// empty. Columns which are not given get empty values. Values are converted into column
+    /// types and nothing is inserted if any row is invalid. This returns the number of inserted
+    /// rows.
+    pub fn insert_rows(
+        &mut self,
+        table_name: &str,
+        columns: &[&str],
+        rows: Vec<Vec<String>>,
+    ) -> CIndexResult<usize> {
+        let columns = columns.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        self.modify_table(table_name, |t| t.insert_rows(&columns, rows))
+    }
+
+    /// Insert rows or update rows whose primary key already exists
+    ///
+    /// Given columns should include every primary key column. Existing row gets values of given
+    /// columns and other columns are kept. This returns the number of inserted and updated
+    /// rows.
+    pub fn upsert_rows(
+        &mut self,
+        table_name: &str,
+        columns: &[&str],
+        rows: Vec<Vec<String>>,
+    ) -> CIndexResult<usize> {
+        let columns = columns.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        self.modify_table(table_name, |t| t.upsert_rows(&columns, rows, true))
+    }
+
+    /// Delete rows of a table which qualify every predicate
+    ///
+    /// Every row is deleted if predicates are empty. This returns the number of deleted rows.
+    pub fn delete_where(
+        &mut self,
+        table_name: &str,
+        predicates: Vec<Predicate>,
+    ) -> CIndexResult<usize> {
+        let query = predicates.into_iter().fold(
+            Query::build().table(table_name).columns(vec!["*"]),
+            |query, predicate| query.predicate(predicate),
+        );
+        self.modify_table(table_name, |t| t.delete(&query))
+    }
+
+    /// Create a hash index of a column
+    ///
+    /// Index is used automatically for "=" and "IN" predicates.
+    pub fn create_index(&mut self, table_name: &str, column: &str) -> CIndexResult<()> {
+        self.create_index_with_type(table_name, column, IndexType::Hash)
+    }
+
+    /// Create an index of a column with given type
+    ///
+    /// Ordered index additionally serves range predicates and ordering of a column.
+    /// This replaces an existing index of the column.
+    pub fn create_index_with_type(
+        &mut self,
+        table_name: &str,
+        column: &str,
+        index_type: IndexType,
+    ) -> CIndexResult<()> {
+        self.modify_table(table_name, |t| t.create_index(column, index_type))
+    }
+
+    /// Drop an index of a column
+    ///
+    /// This returns false if column had no index.
+    pub fn drop_index(&mut self, table_name: &str, column: &str) -> CIndexResult<bool> {
+        self.modify_table(table_name, |t| Ok(t.drop_index(column)))
+    }
+
+    /// Describe how a query would be executed
+    pub fn explain(&self, query: &Query) -> CIndexResult<QueryPlan> {
+        QueryPlan::new(&query.table_name, self.get_table(&query.table_name)?, query)
+    }
+
+    /// Index with raw query which has placeholders
+    ///
+    /// Placeholders are either positional "?" or named ":name".
+    pub fn index_with_params(
+        &self,
+        raw_query: &str,
+        params: &Params,
+        out_option: OutOption,
+    ) -> CIndexResult<()> {
+        self.index(Query::from_str(raw_query)?.bind(params)?, out_option)
+    }
+
+    /// Index with pre-built query
+    pub fn index(&self, query: Query, out_option: OutOption) -> CIndexResult<()> {
+        self.write_records(self.index_table(&query, None)?, out_option)
     }
 
     /// Get rows filtered by query
     pub fn index_get_records(&self, query: Query) -> CIndexResult<Vec<Vec<String>>> {
-        let records = self.index_table(query)?;
+        let records = self.index_table(&query, None)?;
         Ok(records)
     }
 
-    /// Internal function
-    fn index_table(&self, query: Query) -> CIndexResult<Vec<Vec<String>>> {
-        let mut mapped_records: Vec<Vec<String>> = vec![];
-        let table = self.tables.get(query.table_name.as_str()).ok_or_else(|| {
-            CIndexError::InvalidTableName(format!("Table \"{}\" doesn't exist", query.table_name))
-        })?;
+    /// Parse a raw query once and resolve its columns against the table's schema
+    ///
+    /// Prepared query can be executed multiple times with index_prepared.
+    pub fn prepare(&self, raw_query: &str) -> CIndexResult<PreparedQuery> {
+        let query = Query::from_str(raw_query)?;
+        let columns = resolve_columns(self.get_table(&query.table_name)?, &query)?;
+        Ok(PreparedQuery::new(query, columns))
+    }
 
-        // Query
-        let queried_records = table.query(&query)?;
+    /// Index with prepared query
+    ///
+    /// Params are only required when the query has placeholders.
+    pub fn index_prepared(
+        &self,
+        prepared: &PreparedQuery,
+        params: &Params,
+        out_option: OutOption,
+    ) -> CIndexResult<()> {
+        self.write_records(
+            self.index_prepared_table(prepared, params, None)?,
+            out_option,
+        )
+    }
 
-        let mut all_column = false;
-        let mut targets: Vec<ColumnVariant> = vec![];
-        let mut supplment = vec![];
+    /// Index a different table with prepared query
+    ///
+    /// Given table should have columns which prepared query refers to.
+    pub fn index_prepared_on(
+        &self,
+        table_name: &str,
+        prepared: &PreparedQuery,
+        params: &Params,
+        out_option: OutOption,
+    ) -> CIndexResult<()> {
+        self.write_records(
+            self.index_prepared_table(prepared, params, Some(table_name))?,
+            out_option,
+        )
+    }
 
-        for col in query.column_names {
-            if col == "*" {
-                all_column = true;
-                continue;
+    /// Index a csv stream without adding it as a table
+    ///
+    /// Records are read, filtered and written one by one so that a whole stream is never held
+    /// in memory, and reading stops as soon as limit is reached. Records are buffered only when
+    /// query has ORDER BY or TP flag, where ORDER BY with LIMIT keeps only OFFSET + LIMIT rows.
+    /// Query's table name is ignored and every column is text.
+    pub fn query_stream(
+        &self,
+        input: impl BufRead,
+        query: &Query,
+        mut out_option: OutOption,
+    ) -> CIndexResult<()> {
+        if query.has_placeholders() {
+            return Err(CIndexError::InvalidQueryStatement(
+                "Query has placeholders without bound values".to_owned(),
+            ));
+        }
+
+        let mut reader = RecordReader::new(input);
+        let header = reader.next_record()?.ok_or_else(|| {
+            CIndexError::InvalidTableInput("Stream doesn't have a header".to_owned())
+        })?;
+        let columns = header.iter().map(|c| c.as_str()).collect::<Vec<_>>();
+        let position = |column: &str| {
+            columns.iter().position(|c| *c == column).ok_or_else(|| {
+                CIndexError::InvalidColumn(format!(
+                    "Failed to get column \"{}\" from header",
+                    column
+                ))
+            })
+        };
+
+        let targets = target_columns(query, &columns)?;
+        let target_positions = target_positions(&targets, &columns);
+        let predicates = query
+            .predicates
+            .iter()
+            .flatten()
+            .map(|pre| {
+                pre.check_arguments()?;
+                Ok(ResolvedPredicate {
+                    column: position(&pre.column)?,
+                    predicate: pre,
+                    arguments: pre.arguments.iter().cloned().map(Value::Text).collect(),
+                })
+            })
+            .collect::<CIndexResult<Vec<_>>>()?;
+        let order = match &query.order_type {
+            OrderType::Asec(col) => Some((position(col)?, false)),
+            OrderType::Desc(col) => Some((position(col)?, true)),
+            OrderType::None => None,
+        };
+
+        let transpose = query.flags.contains(QueryFlagType::TP);
+        let mut records = vec![];
+        if let Some(header) = header_record(query, &targets)? {
+            if transpose {
+                records.push(header);
+            } else {
+                self.write(&(header.join(",") + self.get_newline()), &mut out_option)?;
             }
-            if let Some(col) = table.header.get(&col) {
-                if !all_column {
-                    targets.push(ColumnVariant::Real(col));
-                }
-            } else if query.flags.contains(QueryFlagType::Sup) {
-                supplment.push(col);
+        }
+
+        let (offset, limit) = query.range;
+        let mut matched = 0;
+        // Only rows within limit are kept for ORDER BY
+        let mut sorted = order.map(|(position, descending)| {
+            let count = if limit == 0 {
+                usize::MAX
             } else {
-                return Err(CIndexError::InvalidQueryStatement(format!(
-                    "Column \"{}\" doesn't exist",
-                    col
+                offset.saturating_add(limit)
+            };
+            TopRows::new(position, descending, count)
+        });
+        while let Some(record) = reader.next_record()? {
+            if record.len() != columns.len() {
+                return Err(CIndexError::InvalidTableInput(format!(
+                    "Row of line \"{}\" has different length.",
+                    reader.line_number()
                 )));
             }
-        }
 
-        // Override target columsn if "*" was given as column name
-        if all_column {
-            targets = table
-                .data
-                .columns
-                .iter()
-                .map(|c| ColumnVariant::Real(c.name.as_str()))
-                .collect();
-        }
+            let row = record.into_iter().map(Value::Text).collect::<Vec<_>>();
+            if !predicates.iter().all(|pre| pre.matches(&row[pre.column])) {
+                continue;
+            }
 
-        // Append supplement columns
-        if !supplment.is_empty() {
-            for sup in supplment {
-                targets.push(ColumnVariant::Supplement(sup));
+            matched += 1;
+            // Every row should be read before sorting
+            if let Some(sorted) = sorted.as_mut() {
+                sorted.push(matched, row);
+                continue;
+            }
+
+            if matched <= offset {
+                continue;
+            }
+            let record = project_values(&row, &target_positions);
+            if transpose {
+                records.push(record);
+            } else {
+                self.write(&(record.join(",") + self.get_newline()), &mut out_option)?;
+            }
+            if limit != 0 && matched >= offset.saturating_add(limit) {
+                break;
             }
         }
 
-        // Print headers
-        if query.flags.contains(QueryFlagType::Phd) {
-            if let Some(map) = query.column_map {
-                if map.len() != targets.len() {
-                    return Err(CIndexError::InvalidQueryStatement(
-                        "Headermap should have a same length with target columns".to_string(),
-                    ));
+        if let Some(sorted) = sorted {
+            for (_, row) in sorted.into_sorted().iter().skip(offset) {
+                let record = project_values(row, &target_positions);
+                if transpose {
+                    records.push(record);
                 } else {
-                    mapped_records.push(map);
+                    self.write(&(record.join(",") + self.get_newline()), &mut out_option)?;
                 }
-            } else {
-                mapped_records.push(targets.iter().map(|col| col.to_string()).collect());
             }
         }
 
-        // Only get target values from rows
-        for record in queried_records {
-            mapped_records.push(self.row_with_columns(record, &targets)?);
+        if transpose && !records.is_empty() {
+            self.write_records(self.tranpose_records(records), out_option)?;
         }
+        Ok(())
+    }
+
+    /// Write records to out option
+    fn write_records(
+        &self,
+        records: Vec<Vec<String>>,
+        mut out_option: OutOption,
+    ) -> CIndexResult<()> {
+        for row in records {
+            self.write(&(row.join(",") + self.get_newline()), &mut out_option)?;
+        }
+        Ok(())
+    }
+
+    /// Get table by name
+    fn get_table(&self, table_name: &str) -> CIndexResult<&Table> {
+        self.tables.get(table_name).ok_or_else(|| {
+            CIndexError::InvalidTableName(format!("Table \"{}\" doesn't exist", table_name))
+        })
+    }
+
+    /// Internal function
+    ///
+    /// Table name overrides query's table name if given.
+    fn index_table(
+        &self,
+        query: &Query,
+        table_name: Option<&str>,
+    ) -> CIndexResult<Vec<Vec<String>>> {
+        let table = self.get_table(table_name.unwrap_or(&query.table_name))?;
+        let columns = resolve_columns(table, query)?;
+        self.run(|| self.index_table_records(table, query, &columns))
+    }
+
+    /// Index a table with a prepared query
+    ///
+    /// Columns are resolved again only if the table's columns differ from the prepared ones.
+    fn index_prepared_table(
+        &self,
+        prepared: &PreparedQuery,
+        params: &Params,
+        table_name: Option<&str>,
+    ) -> CIndexResult<Vec<Vec<String>>> {
+        let query = prepared.bind(params)?;
+        let table = self.get_table(table_name.unwrap_or(&query.table_name))?;
+        if prepared.columns.matches(&table.column_names()) {
+            self.run(|| self.index_table_records(table, &query, &prepared.columns))
+        } else {
+            let columns = resolve_columns(table, &query)?;
+            self.run(|| self.index_table_records(table, &query, &columns))
+        }
+    }
+
+    fn index_table_records(
+        &self,
+        table: &Table,
+        query: &Query,
+        columns: &ResolvedColumns,
+    ) -> CIndexResult<Vec<Vec<String>>> {
+        let mut mapped_records: Vec<Vec<String>> = vec![];
+
+        // Print headers
+        if let Some(header) = &columns.header {
+            mapped_records.push(header.clone());
+        }
+
+        mapped_records.extend(self.resolved_values(table, query, columns)?);
 
         // Tranpose if given TP Flag
-        if query.flags.contains(QueryFlagType::TP) {
+        if query.flags.contains(QueryFlagType::TP) && !mapped_records.is_empty() {
             mapped_records = self.tranpose_records(mapped_records);
         }
 
-        Ok(mapped_records
-            .iter_mut()
-            .map(|vec| vec.iter_mut().map(|val| val.to_string()).collect())
-            .collect())
+        Ok(mapped_records)
+    }
+
+    /// Query a table and get values of target columns
+    ///
+    /// This doesn't include a header and ignores TP flag.
+    fn select_values(&self, table: &Table, query: &Query) -> CIndexResult<Vec<Vec<String>>> {
+        self.resolved_values(table, query, &resolve_columns(table, query)?)
     }
 
-    fn row_with_columns(
+    /// Query a table with resolved columns and get values of target columns
+    fn resolved_values(
         &self,
-        row: &Row,
-        columns: &Vec<ColumnVariant>,
-    ) -> CIndexResult<Vec<String>> {
-        let mut formatted = vec![];
-
-        for col in columns {
-            if let ColumnVariant::Real(key) = col {
-                formatted.push(
-                    row.get_cell_value(key)
-                        .ok_or_else(|| {
-                            CIndexError::InvalidColumn(format!(
-                                "Failed to row value from column \"{}\"",
-                                key
-                            ))
-                        })?
-                        .to_string(),
-                );
-            } else {
-                formatted.push(String::new());
-            }
-        }
-        Ok(formatted)
+        table: &Table,
+        query: &Query,
+        columns: &ResolvedColumns,
+    ) -> CIndexResult<Vec<Vec<String>>> {
+        let queried_records = table.query_resolved(query, columns)?;
+
+        // Only get target values from rows
+        #[cfg(feature = "rayon")]
+        let iter = queried_records.par_iter();
+        #[cfg(not(feature = "rayon"))]
+        let iter = queried_records.iter();
+        Ok(iter
+            .map(|record| record.project(&columns.targets))
+            .collect())
     }
 
     // Tranpose
     // https://stackoverflow.com/questions/64498617/how-to-transpose-a-vector-of-vectors-in-rust
     // Thank you stackoverflow ;)
+    #[cfg(not(feature = "rayon"))]
     fn tranpose_records(&self, v: Vec<Vec<String>>) -> Vec<Vec<String>> {
         let len = v[0].len();
         let mut iters: Vec<_> = v.into_iter().map(|n| n.into_iter()).collect();
@@ -234,6 +988,16 @@ impl Indexer {
             .collect()
     }
 
+    /// Transpose records with every column in parallel
+    #[cfg(feature = "rayon")]
+    fn tranpose_records(&self, v: Vec<Vec<String>>) -> Vec<Vec<String>> {
+        let len = v[0].len();
+        (0..len)
+            .into_par_iter()
+            .map(|index| v.iter().map(|row| row[index].clone()).collect())
+            .collect()
+    }
+
     /// Write content to out option
     fn write(&self, content: &str, out_option: &mut OutOption) -> CIndexResult<()> {
         match out_option {
@@ -245,6 +1009,134 @@ impl Indexer {
     }
 }
 
+/// Resolve query's column names into target columns
+///
+/// Columns should be in order of the table.
+fn target_columns<'a>(query: &Query, columns: &[&'a str]) -> CIndexResult<Vec<ColumnVariant<'a>>> {
+    let mut all_column = false;
+    let mut targets: Vec<ColumnVariant> = vec![];
+    let mut supplment = vec![];
+
+    for col in &query.column_names {
+        if col == "*" {
+            all_column = true;
+            continue;
+        }
+        if let Some(col) = columns.iter().find(|c| *c == col) {
+            if !all_column {
+                targets.push(ColumnVariant::Real(col));
+            }
+        } else if query.flags.contains(QueryFlagType::Sup) {
+            supplment.push(col.to_owned());
+        } else {
+            return Err(CIndexError::InvalidQueryStatement(format!(
+                "Column \"{}\" doesn't exist",
+                col
+            )));
+        }
+    }
+
+    // Override target columsn if "*" was given as column name
+    if all_column {
+        targets = columns.iter().map(|c| ColumnVariant::Real(c)).collect();
+    }
+
+    // Append supplement columns
+    for sup in supplment {
+        targets.push(ColumnVariant::Supplement(sup));
+    }
+    Ok(targets)
+}
+
+/// Resolve target columns into positions of columns
+///
+/// None position is a supplement column.
+fn target_positions(targets: &[ColumnVariant], columns: &[&str]) -> Vec<Option<usize>> {
+    targets
+        .iter()
+        .map(|target| match target {
+            ColumnVariant::Real(col) => columns.iter().position(|c| c == col),
+            ColumnVariant::Supplement(_) => None,
+        })
+        .collect()
+}
+
+/// Result of a table operation which tells whether the table has changed
+trait Modification {
+    fn changed(&self) -> bool;
+}
+
+/// Number of affected rows
+impl Modification for usize {
+    fn changed(&self) -> bool {
+        *self > 0
+    }
+}
+
+/// Whether an operation has found something to change
+impl Modification for bool {
+    fn changed(&self) -> bool {
+        *self
+    }
+}
+
+/// Operation which always changes a table
+impl Modification for () {
+    fn changed(&self) -> bool {
+        true
+    }
+}
+
+/// Validate a query against a table and resolve positions of its columns
+fn resolve_columns(table: &Table, query: &Query) -> CIndexResult<ResolvedColumns> {
+    table.validate(query)?;
+    let columns = table.column_names();
+    let targets = target_columns(query, &columns)?;
+    let (predicates, order) = table.query_columns(query)?;
+    Ok(ResolvedColumns {
+        schema: columns.iter().map(|c| c.to_string()).collect(),
+        header: header_record(query, &targets)?,
+        targets: target_positions(&targets, &columns),
+        predicates,
+        order,
+    })
+}
+
+/// Get header record if query has a print header flag
+fn header_record(query: &Query, targets: &[ColumnVariant]) -> CIndexResult<Option<Vec<String>>> {
+    if !query.flags.contains(QueryFlagType::Phd) {
+        return Ok(None);
+    }
+    header_names(query, targets).map(Some)
+}
+
+/// Get names of target columns with header map considered
+fn header_names(query: &Query, targets: &[ColumnVariant]) -> CIndexResult<Vec<String>> {
+    if let Some(map) = &query.column_map {
+        if map.len() != targets.len() {
+            return Err(CIndexError::InvalidQueryStatement(
+                "Headermap should have a same length with target columns".to_string(),
+            ));
+        }
+        Ok(map.clone())
+    } else {
+        Ok(targets.iter().map(|col| col.to_string()).collect())
+    }
+}
+
+/// Get values of target positions from a row
+///
+/// None position is a supplement column which is always empty.
+fn project_values(row: &[Value], positions: &[Option<usize>]) -> Vec<String> {
+    positions
+        .iter()
+        .map(|position| match position {
+            Some(position) => row[*position].to_string(),
+            None => String::new(),
+        })
+        .collect()
+}
+
 /// Ouput redirect option
 pub enum OutOption<'a> {
     Term,
@@ -3,8 +3,8 @@
 //! Cindex is a easy to use csv indexer with SQL-like simple query support.
 //!
 //! Cindex is not intended for heavy database indexing but for simple in-memory
-//! querying. Use other databases interaction layer if you're using big chunks of
-//! csv files.
+//! querying. Use Indexer::query_stream for big chunks of csv files which should not be loaded
+//! into memory.
 //!
 //! # Usage
 //!
@@ -13,13 +13,15 @@
 //! cindex = "*" # Use the latest version if possible
 //!
 //! # Use "default-features = false" if you don't need rayon iteration enhancement.
+//! # Use "serde" feature to serialize and deserialize queries.
+//! # Use "mmap" feature to add memory mapped tables with Indexer::add_table_mapped.
 //! ```
 //!
 //! ```no_run
 //! use std::fs::File;
 //! use std::io::BufReader;
 //! use std::str::FromStr;
-//! use cindex::{Indexer, Predicate, Query, OutOption, Operator};
+//! use cindex::{Constraint, Indexer, Params, Predicate, Query, OutOption, Operator, QuoteStyle, WriterOption};
 //!
 //! let mut indexer = Indexer::new();
 //!
@@ -54,6 +56,15 @@
 //!     )
 //!     .expect("Failed to index a table");
 //!
+//! // Bind values into placeholders instead of formatting a raw query
+//! indexer
+//!     .index_with_params(
+//!         "SELECT * FROM table1 WHERE id = ? AND address = :address",
+//!         &Params::new().arg(10).named("address", "111-2222"),
+//!         OutOption::Term,
+//!     )
+//!     .expect("Failed to index a table");
+//!
 //! // Use builder pattern to construct query and index a table
 //! let query = Query::build()
 //!     .table("table2")
@@ -71,6 +82,89 @@
 //!     .index(query, OutOption::Value(&mut acc))
 //!     .expect("Failed to index a table");
 //!
+//! // Create a hash index and execute statements which modify an indexer
+//! indexer
+//!     .create_index("table1", "id")
+//!     .expect("Failed to create an index");
+//! indexer
+//!     .execute_raw("CREATE INDEX ON table3 (id)", OutOption::Term)
+//!     .expect("Failed to execute a statement");
+//!
+//! // Insert rows into a table
+//! indexer
+//!     .insert_rows("table1", &["id", "address"], vec![vec!["11".to_owned(), "333-4444".to_owned()]])
+//!     .expect("Failed to insert rows");
+//!
+//! // Delete rows of a table which qualify predicates
+//! indexer
+//!     .delete_where(
+//!         "table1",
+//!         vec![Predicate::build().column("id").operator(Operator::Equal).raw_args("11")],
+//!     )
+//!     .expect("Failed to delete rows");
+//!
+//! // Store a query's result as a new table with column types kept
+//! indexer
+//!     .materialize("table4", &Query::from_str("SELECT id,address FROM table1 WHERE id > 10").expect("Failed to create query"))
+//!     .expect("Failed to materialize a query");
+//!
+//! // Write current rows of a table including edits as csv
+//! indexer
+//!     .save_table(
+//!         "table1",
+//!         File::create("edited.csv").expect("Failed to create a file"),
+//!         &WriterOption::new().delimiter(';').quote_style(QuoteStyle::Always),
+//!     )
+//!     .expect("Failed to save a table");
+//!
+//! // Roll back every modification if any statement fails
+//! indexer
+//!     .transaction(|tx| {
+//!         tx.execute_raw("DELETE FROM table1 WHERE id = 11", OutOption::Term)?;
+//!         tx.execute_raw("UPDATE table1 SET address = TRIM(address)", OutOption::Term)
+//!     })
+//!     .expect("Failed to run a transaction");
+//!
+//! // Keep up to 10 snapshots of each table to undo modifications
+//! indexer.set_history_limit(10);
+//! indexer
+//!     .execute_raw("UPDATE table1 SET address = 'unknown'", OutOption::Term)
+//!     .expect("Failed to update a table");
+//! indexer.undo("table1").expect("Failed to undo");
+//!
+//! // Reject rows which violate constraints on load and on every modification
+//! indexer
+//!     .add_table_with_constraints(
+//!         "vendor",
+//!         BufReader::new(File::open("vendor.csv").expect("Failed to open a file")),
+//!         vec![
+//!             Constraint::not_null("id"),
+//!             Constraint::unique(vec!["id"]),
+//!             Constraint::check("status IN (open, closed)").expect("Failed to create a constraint"),
+//!         ],
+//!     )
+//!     .expect("Failed to add a table");
+//!
+//! // Identify rows with a primary key and look up a row by its key
+//! indexer
+//!     .set_primary_key("vendor", &["id"])
+//!     .expect("Failed to set a primary key");
+//! let vendor = indexer
+//!     .get_by_key("vendor", &["10"])
+//!     .expect("Failed to look up a row");
+//! if let Some(row) = vendor {
+//!     println!("{:?}", row.values.get("status"));
+//! }
+//!
+//! // Index a large csv file without adding it as a table
+//! indexer
+//!     .query_stream(
+//!         BufReader::new(File::open("large.csv").expect("Failed to open a file")),
+//!         &Query::from_str("SELECT id FROM large WHERE id > 10 LIMIT 5").expect("Failed to create query"),
+//!         OutOption::Term,
+//!     )
+//!     .expect("Failed to index a stream");
+//!
 //! // Always use unix newline for formatting
 //! indexer.always_use_unix_newline(true);
 //!
@@ -100,6 +194,50 @@
 //! condition and also other column's value matches regex expression */
 //! SELECT col1,col2 FROM table1 WHERE col1 = 10 AND col2 LIKE ^start
 //!
+//! /* Create a hash index of a column which serves "=" and "IN" predicates */
+//! CREATE INDEX ON table1 (col1)
+//!
+//! /* Create an ordered index which also serves range predicates and ORDER BY */
+//! CREATE INDEX ON table1 (col2) USING BTREE
+//!
+//! /* Describe how a query would be executed instead of executing it */
+//! EXPLAIN SELECT col1 FROM table1 WHERE col1 = 10 ORDER BY col2
+//!
+//! /* Insert rows into a table, columns which are not given get empty values */
+//! INSERT INTO table1 (col1,col2) VALUES (1, 'first value'), (2, second)
+//!
+//! /* Insert queried rows into a table */
+//! INSERT INTO table1 SELECT * FROM table2 WHERE col1 > 10
+//!
+//! /* Update rows which qualify predicates, functions are UPPER, LOWER and TRIM of a column */
+//! UPDATE table1 SET col1 = 'closed', col2 = UPPER(col2) WHERE col3 > 30
+//!
+//! /* Delete rows which qualify predicates, every row is deleted without WHERE */
+//! DELETE FROM table1 WHERE col1 = 'invalid'
+//!
+//! /* Alter a schema of a table, added column is a text column */
+//! ALTER TABLE table1 ADD COLUMN col4 DEFAULT 'none'
+//! ALTER TABLE table1 DROP COLUMN col3
+//! ALTER TABLE table1 RENAME COLUMN col1 TO id
+//! ALTER TABLE table1 RENAME TO table2
+//!
+//! /* Store a query's result as a new table */
+//! CREATE TABLE table2 AS SELECT col1,col2 FROM table1 WHERE col1 > 10
+//!
+//! /* Apply a batch of statements completely or not at all */
+//! BEGIN
+//! DELETE FROM table1 WHERE col1 = 'invalid'
+//! UPDATE table1 SET col2 = TRIM(col2)
+//! COMMIT /* or ROLLBACK */
+//!
+//! /* Undo or redo the latest modification of a table, which requires Indexer::set_history_limit */
+//! UNDO table1
+//! REDO table1
+//!
+//! /* Insert rows or update rows whose primary key exists, which requires Indexer::set_primary_key */
+//! INSERT INTO table1 (col1,col2) VALUES (1, a), (2, b) ON CONFLICT DO UPDATE /* or DO NOTHING */
+//! UPSERT INTO table1 (col1,col2) VALUES (1, a)
+//!
 //! /* There is a flag syntax which changes query behaviour*/
 //! SELECT * FROM table_name FLAG PHD SUP
 //!
@@ -118,24 +256,40 @@
 //!  <
 //!  =
 //!  !=
-//!  IN ( enumerate )
-//!  BETWEEN (inclusive range of min & max)
+//!  IN (a, b, 'c d') ( enumerate )
+//!  BETWEEN 1 AND 10 (inclusive range of min & max)
 //!  LIKE ( with regeular expression )
 //! ```
 
+mod cache;
 #[cfg(feature = "binary")]
 mod cli;
+mod columnar;
+mod constraint;
 mod consts;
 mod error;
+mod history;
+mod index;
 mod indexer;
+#[cfg(feature = "mmap")]
+mod mapped;
 mod models;
 mod parser;
+mod plan;
 mod query;
+mod statement;
+mod stats;
+mod stream;
 mod table;
 #[cfg(test)]
 mod test;
+mod writer;
 
-pub use dcsv::ReaderOption;
+pub use constraint::{Constraint, Violation};
+pub use dcsv::{ReaderOption, Row, Value};
 pub use error::{CIndexError, CIndexResult};
+pub use index::IndexType;
 pub use indexer::{Indexer, OutOption};
-pub use query::{Operator, Predicate, Query};
+pub use plan::{AccessPath, FilterStep, QueryPlan, SortStep};
+pub use query::{Operator, Params, Predicate, PreparedQuery, Query};
+pub use writer::{QuoteStyle, WriterOption};
@@ -0,0 +1,111 @@
+//! Memory mapped table which decodes records on demand
+//!
+//! Only a header and byte ranges of records are kept in memory. A record is read from the
+//! mapped file when it is filtered or projected.
+
+use crate::{CIndexError, CIndexResult};
+use dcsv::utils::csv_row_to_vector;
+use dcsv::Value;
+use memmap2::Mmap;
+use std::fs::File;
+use std::path::Path;
+
+pub(crate) struct MappedData {
+    map: Mmap,
+    pub(crate) columns: Vec<String>,
+    // Byte range of every record without a newline
+    records: Vec<(usize, usize)>,
+}
+
+impl MappedData {
+    /// Map a csv file and find ranges of its records
+    ///
+    /// This follows the same reader options with a default table, which consumes double quotes
+    /// and ignores empty rows. Every record is checked to have the same length with the header.
+    pub fn open(path: &Path) -> CIndexResult<Self> {
+        let file = File::open(path)?;
+        // Mapped file should not be modified while it is mapped, which is the same assumption
+        // with a cached table that is validated against its source file.
+        let map = unsafe { Mmap::map(&file)? };
+        let text = std::str::from_utf8(&map).map_err(|err| {
+            CIndexError::InvalidTableInput(format!("Table is not a valid utf8 text : {}", err))
+        })?;
+
+        let bytes = text.as_bytes();
+        let mut columns: Option<Vec<String>> = None;
+        let mut records = vec![];
+        let mut start = 0;
+        let mut fields = 1;
+        let mut quoted = false;
+        let mut line_number = 0;
+
+        // Virtual newline at the end terminates the last record
+        for index in 0..=bytes.len() {
+            let byte = bytes.get(index).copied().unwrap_or(b'\n');
+            match byte {
+                b'"' => quoted = !quoted,
+                b',' if !quoted => fields += 1,
+                b'\n' => {
+                    line_number += 1;
+                    if quoted {
+                        continue;
+                    }
+                    let mut end = index;
+                    if end > start && bytes[end - 1] == b'\r' {
+                        end -= 1;
+                    }
+                    let record = &text[start..end];
+                    if !record.trim().is_empty() {
+                        match &columns {
+                            None => columns = Some(csv_row_to_vector(record, None, true)),
+                            Some(columns) if columns.len() != fields => {
+                                return Err(CIndexError::InvalidTableInput(format!(
+                                    "Row of line \"{}\" has different length.",
+                                    line_number
+                                )))
+                            }
+                            Some(_) => records.push((start, end)),
+                        }
+                    }
+                    start = index + 1;
+                    fields = 1;
+                }
+                _ => (),
+            }
+        }
+
+        if quoted {
+            return Err(CIndexError::InvalidTableInput(format!(
+                "Record of line \"{}\" has unterminated double quote",
+                line_number
+            )));
+        }
+
+        let columns = columns.ok_or_else(|| {
+            CIndexError::InvalidTableInput("Table doesn't have a header".to_owned())
+        })?;
+        Ok(Self {
+            map,
+            columns,
+            records,
+        })
+    }
+
+    /// Get the number of records
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Decode values of a record
+    ///
+    /// Every value is a text because mapped table doesn't have column types.
+    pub fn record(&self, position: usize) -> Vec<Value> {
+        let (start, end) = self.records[position];
+        // Whole file was validated as utf8 on open
+        let record = std::str::from_utf8(&self.map[start..end]).unwrap_or_default();
+        csv_row_to_vector(record, None, true)
+            .into_iter()
+            .map(Value::Text)
+            .collect()
+    }
+}
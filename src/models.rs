@@ -2,8 +2,11 @@ use std::fmt::Display;
 
 use crate::error::CIndexError;
 use crate::CIndexResult;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum OrderType {
     None,
     Asec(String),
@@ -1,5 +1,8 @@
-use crate::query::{Operator, Predicate, Query, QueryFlags, Separator};
+use crate::index::IndexType;
+use crate::query::{Operator, Placeholder, Predicate, Query, QueryFlags, Separator};
+use crate::statement::{Alteration, Assignment, Conflict, Expression, InsertSource, Statement};
 use crate::{models::OrderType, CIndexError, CIndexResult};
+use std::borrow::Cow;
 
 pub struct Parser {
     cursor: ParseCursor,
@@ -46,18 +49,204 @@ impl Parser {
         }
     }
 
-    pub fn parse(&mut self, query: &str) -> CIndexResult<Query> {
-        let split = tokens_with_quote(query).into_iter();
+    /// Parse a statement which may have a leading keyword other than SELECT
+    pub fn parse_statement(&mut self, source: &str) -> CIndexResult<Statement> {
+        if let Some(rest) = strip_keyword(source, "explain") {
+            return Ok(Statement::Explain(self.parse(rest)?));
+        }
+        if let Some(rest) = strip_keyword(source, "create").and_then(|s| strip_keyword(s, "index"))
+        {
+            let (rest, index_type) = match rest.to_ascii_lowercase().rfind(" using ") {
+                Some(pos) => (&rest[..pos], parse_index_type(&rest[pos + 7..])?),
+                None => (rest, IndexType::Hash),
+            };
+            let (table, column) = parse_index_target(rest)?;
+            return Ok(Statement::CreateIndex {
+                table,
+                column,
+                index_type,
+            });
+        }
+        if let Some(rest) = strip_keyword(source, "create").and_then(|s| strip_keyword(s, "table"))
+        {
+            let rest = rest.trim_start();
+            let table_end = rest
+                .find(|ch: char| ch.is_whitespace())
+                .unwrap_or(rest.len());
+            let query = strip_keyword(&rest[table_end..], "as").ok_or_else(|| {
+                CIndexError::InvalidQueryStatement(format!(
+                    "Create table statement should be \"CREATE TABLE table AS SELECT ...\" but given \"{}\"",
+                    rest
+                ))
+            })?;
+            return Ok(Statement::CreateTable {
+                table: unquote(&rest[..table_end]),
+                query: self.parse(query)?,
+            });
+        }
+        if let Some(rest) = strip_keyword(source, "drop").and_then(|s| strip_keyword(s, "index")) {
+            let (table, column) = parse_index_target(rest)?;
+            return Ok(Statement::DropIndex { table, column });
+        }
+        if let Some(rest) = strip_keyword(source, "insert").and_then(|s| strip_keyword(s, "into")) {
+            return self.parse_insert(rest);
+        }
+        if let Some(rest) = strip_keyword(source, "upsert").and_then(|s| strip_keyword(s, "into")) {
+            return match self.parse_insert(rest)? {
+                Statement::Insert {
+                    table,
+                    columns,
+                    source,
+                    on_conflict: None,
+                } => Ok(Statement::Insert {
+                    table,
+                    columns,
+                    source,
+                    on_conflict: Some(Conflict::Update),
+                }),
+                _ => Err(CIndexError::InvalidQueryStatement(
+                    "Upsert statement cannot have ON CONFLICT clause".to_owned(),
+                )),
+            };
+        }
+        if let Some(rest) = strip_keyword(source, "update") {
+            return self.parse_update(rest);
+        }
+        if let Some(rest) = strip_keyword(source, "delete").and_then(|s| strip_keyword(s, "from")) {
+            return Ok(Statement::Delete(
+                self.parse(&format!("SELECT * FROM {}", rest))?,
+            ));
+        }
+        if let Some(rest) = strip_keyword(source, "alter").and_then(|s| strip_keyword(s, "table")) {
+            return parse_alter(rest);
+        }
+        if let Some(rest) = strip_keyword(source, "undo") {
+            return Ok(Statement::Undo(parse_table_name(rest)?));
+        }
+        if let Some(rest) = strip_keyword(source, "redo") {
+            return Ok(Statement::Redo(parse_table_name(rest)?));
+        }
+        // Transaction keyword may be followed by an optional TRANSACTION
+        let transaction = |keyword: &str| {
+            strip_keyword(source, keyword).map(|rest| {
+                let rest = rest.trim();
+                rest.is_empty() || rest.eq_ignore_ascii_case("transaction")
+            }) == Some(true)
+        };
+        if transaction("begin") {
+            return Ok(Statement::Begin);
+        }
+        if transaction("commit") {
+            return Ok(Statement::Commit);
+        }
+        if transaction("rollback") {
+            return Ok(Statement::Rollback);
+        }
+        Ok(Statement::Select(self.parse(source)?))
+    }
+
+    /// Parse "table [(columns)] VALUES (values), ..." or "table [(columns)] SELECT ..."
+    ///
+    /// Both can end with "ON CONFLICT DO NOTHING" or "ON CONFLICT DO UPDATE".
+    fn parse_insert(&mut self, source: &str) -> CIndexResult<Statement> {
+        let (source, on_conflict) = split_conflict(source)?;
+        let invalid = || {
+            CIndexError::InvalidQueryStatement(format!(
+                "Insert statement should be \"INSERT INTO table [(columns)] VALUES (values)\" or \"INSERT INTO table [(columns)] SELECT ...\" but given \"{}\"",
+                source.trim()
+            ))
+        };
+        // Whichever comes first is the keyword of the statement
+        let (position, select) = match (
+            keyword_position(source, "values"),
+            keyword_position(source, "select"),
+        ) {
+            (Some(values), Some(select)) if select < values => (select, true),
+            (Some(values), _) => (values, false),
+            (None, Some(select)) => (select, true),
+            (None, None) => return Err(invalid()),
+        };
+        let (target, rows) = source.split_at(position);
+
+        let target = target.trim();
+        let table_end = target
+            .find(|ch: char| ch.is_whitespace() || ch == '(')
+            .unwrap_or(target.len());
+        let table = unquote(&target[..table_end]);
+        if table.is_empty() {
+            return Err(invalid());
+        }
+        let columns = list_items(&target[table_end..])
+            .iter()
+            .map(|c| unquote(c.trim()))
+            .collect();
+
+        let source = if select {
+            InsertSource::Select(self.parse(rows)?)
+        } else {
+            let values = strip_keyword(rows, "values").ok_or_else(invalid)?;
+            InsertSource::Values(value_groups(values).ok_or_else(invalid)?)
+        };
+        Ok(Statement::Insert {
+            table,
+            columns,
+            source,
+            on_conflict,
+        })
+    }
+
+    /// Parse "table SET column = expression, ... [WHERE predicates]"
+    fn parse_update(&mut self, source: &str) -> CIndexResult<Statement> {
+        let invalid = || {
+            CIndexError::InvalidQueryStatement(format!(
+                "Update statement should be \"UPDATE table SET column = expression, ... [WHERE predicates]\" but given \"{}\"",
+                source.trim()
+            ))
+        };
+        let source = source.trim_start();
+        let table_end = source
+            .find(|ch: char| ch.is_whitespace())
+            .unwrap_or(source.len());
+        let table = unquote(&source[..table_end]);
+        let rest = strip_keyword(&source[table_end..], "set").ok_or_else(invalid)?;
+        let (assignments, predicates) = match keyword_position(rest, "where") {
+            Some(position) => rest.split_at(position),
+            None => (rest, ""),
+        };
 
+        let mut start = 0;
+        let mut ends = separator_positions(assignments, ',');
+        ends.push(assignments.len());
+        let assignments = ends
+            .into_iter()
+            .map(|end| {
+                let assignment = &assignments[start..end];
+                start = end + 1;
+                let equal = *separator_positions(assignment, '=')
+                    .first()
+                    .ok_or_else(invalid)?;
+                let column = unquote(assignment[..equal].trim());
+                if column.is_empty() {
+                    return Err(invalid());
+                }
+                Ok(Assignment {
+                    column,
+                    expression: parse_expression(assignment[equal + 1..].trim())?,
+                })
+            })
+            .collect::<CIndexResult<Vec<_>>>()?;
+
+        // Predicates are parsed as a query of the table
+        let query = self.parse(&format!("SELECT * FROM {} {}", quote(&table), predicates))?;
+        Ok(Statement::Update { assignments, query })
+    }
+
+    pub fn parse(&mut self, query: &str) -> CIndexResult<Query> {
         // SELECT columns FROM TABLE WHERE arguments
-        for word in split {
-            let word = word.trim();
-            if word.is_empty() {
-                continue;
-            }
-            // If no cursor change, then
-            if !self.set_cursor(word) {
-                self.update_state(word)?;
+        for token in raw_tokens(query) {
+            // Quoted token is never a keyword
+            if is_quoted(&token) || !self.set_cursor(&token) {
+                self.update_state(&token)?;
             }
         }
 
@@ -78,7 +267,7 @@ impl Parser {
         }
 
         let predicates = self.get_predicates()?;
-        let joined = std::mem::replace(&mut self.state.joined, None);
+        let joined = self.state.joined.take();
         let order_type = match self.state.order_by.len() {
             1 => OrderType::from_str("ASEC", &self.state.order_by[0])?, // Default ordering is ASEC
             2 => OrderType::from_str(&self.state.order_by[1], &self.state.order_by[0])?,
@@ -88,7 +277,10 @@ impl Parser {
             _ => OrderType::None,
         };
 
-        let column_map = std::mem::replace(&mut self.state.raw_column_map, None)
+        let column_map = self
+            .state
+            .raw_column_map
+            .take()
             .map(|s| s.split(',').map(|s| s.to_owned()).collect::<Vec<String>>());
 
         // Split
@@ -135,7 +327,19 @@ impl Parser {
         }
     }
 
-    fn update_state(&mut self, arg: &str) -> CIndexResult<()> {
+    fn update_state(&mut self, token: &str) -> CIndexResult<()> {
+        // Where arguments are kept raw because quotes are meaningful for predicates
+        if self.cursor == ParseCursor::Where {
+            self.state.where_args.push(token.to_owned());
+            return Ok(());
+        }
+
+        let arg = unquote(token);
+        if arg.is_empty() {
+            return Ok(());
+        }
+        let arg = arg.as_str();
+
         match self.cursor {
             ParseCursor::From => {
                 self.state.table_name = arg.to_owned();
@@ -144,11 +348,8 @@ impl Parser {
                 // This is safe to use unwrap
                 self.state.raw_column_names = arg.to_owned();
             }
-            ParseCursor::Where => {
-                self.state.where_args.push(arg.to_owned());
-            }
             ParseCursor::Join => {
-                if self.state.joined == None {
+                if self.state.joined.is_none() {
                     self.state.joined.replace(vec![]);
                 }
                 // This is safe to use unwrap
@@ -159,7 +360,7 @@ impl Parser {
                 self.state.order_by.push(arg.to_owned());
             }
             ParseCursor::Hmap => {
-                if self.state.raw_column_map == None {
+                if self.state.raw_column_map.is_none() {
                     self.state.raw_column_map.replace(String::new());
                 }
                 // This is safe to use unwrap
@@ -182,7 +383,7 @@ impl Parser {
                     )
                 })?;
             }
-            ParseCursor::None | ParseCursor::OrderBy(false) => (),
+            ParseCursor::None | ParseCursor::OrderBy(false) | ParseCursor::Where => (),
         }
         Ok(())
     }
@@ -192,9 +393,18 @@ impl Parser {
         let mut predicates = vec![];
         let mut p = Predicate::build();
         let mut w_cursor = WhereCursor::Left;
+        let mut positional = 0;
 
         for token in &self.state.where_args {
             if let Some(sep) = self.find_separator(token) {
+                // "AND" between BETWEEN's arguments is a part of the predicate
+                if p.operation == Operator::Between
+                    && p.arguments.len() == 1
+                    && matches!(sep, Separator::And)
+                {
+                    continue;
+                }
+
                 Self::predicate_chore(&mut p)?;
 
                 if !p.column.is_empty() {
@@ -212,15 +422,30 @@ impl Parser {
 
             match w_cursor {
                 WhereCursor::Left => {
-                    p.set_column(token);
+                    p.set_column(&unquote(token));
                     w_cursor = WhereCursor::Operator;
                 }
                 WhereCursor::Operator => {
-                    p.set_operator(Operator::from_token(token)?);
+                    p.set_operator(Operator::from_token(&unquote(token))?);
                     w_cursor = WhereCursor::Right;
                 }
                 WhereCursor::Right => {
-                    p.add_arg(token);
+                    let items = if p.operation == Operator::In {
+                        list_items(token)
+                    } else {
+                        vec![token.to_owned()]
+                    };
+                    for item in items {
+                        let placeholder = if is_quoted(&item) {
+                            None
+                        } else {
+                            Placeholder::from_token(&item, &mut positional)
+                        };
+                        match placeholder {
+                            Some(placeholder) => p.add_placeholder(placeholder),
+                            None => p.add_arg(&unquote(&item)),
+                        }
+                    }
                 }
             }
         }
@@ -236,84 +461,316 @@ impl Parser {
 
     /// Apply chores for predicate
     fn predicate_chore(predicate: &mut Predicate) -> CIndexResult<()> {
+        predicate.check_arguments()?;
+
         // Precompile regex
-        if Operator::Like == predicate.operation {
-            predicate.set_matcher(
-                &predicate
-                    .arguments
-                    .last()
-                    .ok_or_else(|| {
-                        CIndexError::InvalidQueryStatement(
-                            "Like requires argument to be a valid query".to_owned(),
-                        )
-                    })?
-                    .clone(),
-            )?;
-        }
-
-        if predicate.arguments.is_empty() {
-            return Err(CIndexError::InvalidQueryStatement(format!(
-                "Query has insufficient arguments : {:?}",
-                predicate
-            )));
+        // Placeholder's regex is compiled when a value is bound
+        if Operator::Like == predicate.operation && predicate.placeholders.is_empty() {
+            let pattern = predicate.arguments[0].clone();
+            predicate.set_matcher(&pattern)?;
         }
 
         Ok(())
     }
 
     fn find_separator(&self, token: &str) -> Option<Separator> {
-        match token {
-            "AND" => Some(Separator::And),
-            "OR" => Some(Separator::Or),
-            _ => None,
+        if token.eq_ignore_ascii_case("AND") {
+            Some(Separator::And)
+        } else if token.eq_ignore_ascii_case("OR") {
+            Some(Separator::Or)
+        } else {
+            None
         }
     }
 }
 
-/// Parse string with quote-able tokens
+/// Strip a leading keyword from source
 ///
-/// This strips surround quote.
-fn tokens_with_quote(source: &str) -> Vec<String> {
-    let mut tokens = vec![];
+/// This returns none if source doesn't start with the keyword.
+fn strip_keyword<'a>(source: &'a str, keyword: &str) -> Option<&'a str> {
+    let source = source.trim_start();
+    let end = source
+        .find(|ch: char| ch.is_whitespace())
+        .unwrap_or(source.len());
+    if source[..end].eq_ignore_ascii_case(keyword) {
+        Some(&source[end..])
+    } else {
+        None
+    }
+}
+
+/// Split a trailing "ON CONFLICT DO NOTHING|UPDATE" clause from source
+fn split_conflict(source: &str) -> CIndexResult<(&str, Option<Conflict>)> {
+    let mut rest = source.trim_end();
+    let mut words = vec![];
+    for _ in 0..4 {
+        let start = rest
+            .char_indices()
+            .rev()
+            .find(|(_, ch)| ch.is_whitespace())
+            .map(|(index, ch)| index + ch.len_utf8())
+            .unwrap_or(0);
+        words.insert(0, &rest[start..]);
+        rest = rest[..start].trim_end();
+    }
+    let is_clause = words
+        .iter()
+        .zip(["on", "conflict", "do"])
+        .all(|(word, keyword)| word.eq_ignore_ascii_case(keyword));
+    if !is_clause {
+        return Ok((source, None));
+    }
+    let conflict = match words[3].to_lowercase().as_str() {
+        "nothing" => Conflict::Nothing,
+        "update" => Conflict::Update,
+        other => {
+            return Err(CIndexError::InvalidQueryStatement(format!(
+                "Conflict can only be resolved with DO NOTHING or DO UPDATE but given \"DO {}\"",
+                other
+            )))
+        }
+    };
+    Ok((rest, Some(conflict)))
+}
+
+/// Find a byte position of an unquoted keyword in source
+fn keyword_position(source: &str, keyword: &str) -> Option<usize> {
     let mut on_quote = false;
-    let mut previous = ' ';
-    let mut chunk = String::new();
-    let iter = source.chars().peekable();
-    for ch in iter {
+    let mut escaped = false;
+    let mut word_start = None;
+    // Trailing whitespace terminates the last word
+    for (index, ch) in source
+        .char_indices()
+        .chain(std::iter::once((source.len(), ' ')))
+    {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        if !on_quote && (ch.is_whitespace() || matches!(ch, '(' | ')' | ',')) {
+            if let Some(start) = word_start.take() {
+                if source[start..index].eq_ignore_ascii_case(keyword) {
+                    return Some(start);
+                }
+            }
+            continue;
+        }
+        word_start.get_or_insert(index);
         match ch {
-            // Escape character should not bed added
-            '\\' => {
-                if previous == '\\' {
-                    previous = ' '; // Reset previous
-                } else {
-                    on_quote = !on_quote;
-                    previous = ch;
+            '\\' => escaped = true,
+            '\'' => on_quote = !on_quote,
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Find byte positions of a separator which is neither quoted nor inside parenthesis
+fn separator_positions(source: &str, separator: char) -> Vec<usize> {
+    let mut positions = vec![];
+    let mut on_quote = false;
+    let mut escaped = false;
+    let mut depth = 0usize;
+    for (index, ch) in source.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match ch {
+            '\\' => escaped = true,
+            '\'' => on_quote = !on_quote,
+            '(' if !on_quote => depth += 1,
+            ')' if !on_quote => depth = depth.saturating_sub(1),
+            _ if ch == separator && !on_quote && depth == 0 => positions.push(index),
+            _ => (),
+        }
+    }
+    positions
+}
+
+/// Parse a literal value or "FUNCTION(column)" of an assignment
+fn parse_expression(source: &str) -> CIndexResult<Expression> {
+    if source.is_empty() {
+        return Err(CIndexError::InvalidQueryStatement(
+            "Assignment requires a value".to_owned(),
+        ));
+    }
+    let function = source
+        .find('(')
+        .filter(|_| !source.starts_with('\'') && source.ends_with(')'));
+    let open = match function {
+        Some(open) => open,
+        None => return Ok(Expression::Literal(unquote(source))),
+    };
+    let column = unquote(source[open + 1..source.len() - 1].trim());
+    match source[..open].trim().to_lowercase().as_str() {
+        "upper" => Ok(Expression::Upper(column)),
+        "lower" => Ok(Expression::Lower(column)),
+        "trim" => Ok(Expression::Trim(column)),
+        name => Err(CIndexError::InvalidQueryStatement(format!(
+            "Function can only be UPPER, LOWER or TRIM but given \"{}\"",
+            name
+        ))),
+    }
+}
+
+/// Split "(a, b), (c, d)" into literal values of each group
+///
+/// This returns none if source is not a comma separated list of groups.
+fn value_groups(source: &str) -> Option<Vec<Vec<String>>> {
+    let mut groups = vec![];
+    let mut on_quote = false;
+    let mut group: Option<String> = None;
+    let mut expects_group = true;
+    let mut iter = source.chars();
+    while let Some(ch) = iter.next() {
+        if let Some(chunk) = group.as_mut() {
+            match ch {
+                '\\' => {
+                    chunk.push(ch);
+                    if let Some(next) = iter.next() {
+                        chunk.push(next);
+                    }
                     continue;
                 }
-            }
-            '\'' => {
-                // Add literal quote if previous was escape character
-                if previous == '\\' {
-                    previous = ' '; // Reset previous
-                } else {
-                    on_quote = !on_quote;
-                    previous = ch;
+                '\'' => on_quote = !on_quote,
+                ')' if !on_quote => {
+                    let items = list_items(chunk);
+                    groups.push(items.iter().map(|item| unquote(item.trim())).collect());
+                    group = None;
                     continue;
                 }
+                _ => (),
             }
-            ' ' => {
-                if !on_quote {
-                    // If previous is also blank. skip
-                    if previous == ' ' {
-                        continue;
-                    }
-                    let flushed = std::mem::take(&mut chunk);
-                    tokens.push(flushed);
-                    previous = ch;
-                    continue;
+            chunk.push(ch);
+            continue;
+        }
+        match ch {
+            '(' if expects_group => {
+                group.replace(String::new());
+                expects_group = false;
+            }
+            ',' if !expects_group => expects_group = true,
+            _ if ch.is_whitespace() => (),
+            _ => return None,
+        }
+    }
+    if group.is_some() || expects_group {
+        return None;
+    }
+    Some(groups)
+}
+
+/// Parse "table ADD|DROP|RENAME ..." of an alter table statement
+fn parse_alter(source: &str) -> CIndexResult<Statement> {
+    let invalid = || {
+        CIndexError::InvalidQueryStatement(format!(
+            "Alter statement should be \"ALTER TABLE table\" with \"ADD COLUMN column [DEFAULT value]\", \"DROP COLUMN column\", \"RENAME COLUMN column TO name\" or \"RENAME TO name\" but given \"{}\"",
+            source.trim()
+        ))
+    };
+    let tokens = raw_tokens(source);
+    // Quoted token is never a keyword
+    let is_keyword = |index: usize, keyword: &str| matches!(tokens.get(index), Some(token) if !is_quoted(token) && token.eq_ignore_ascii_case(keyword));
+    let literal = |index: usize| tokens.get(index).map(|token| unquote(token));
+    let table = literal(0).ok_or_else(invalid)?;
+
+    // COLUMN keyword is optional
+    let column = if is_keyword(2, "column") { 3 } else { 2 };
+    let alteration = if is_keyword(1, "add") {
+        let default = match tokens.len() - column {
+            1 => String::new(),
+            3 if is_keyword(column + 1, "default") => literal(column + 2).ok_or_else(invalid)?,
+            _ => return Err(invalid()),
+        };
+        Alteration::AddColumn {
+            column: literal(column).ok_or_else(invalid)?,
+            default,
+        }
+    } else if is_keyword(1, "drop") && tokens.len() == column + 1 {
+        Alteration::DropColumn(literal(column).ok_or_else(invalid)?)
+    } else if is_keyword(1, "rename") && is_keyword(2, "to") && tokens.len() == 4 {
+        Alteration::RenameTable(literal(3).ok_or_else(invalid)?)
+    } else if is_keyword(1, "rename") && is_keyword(column + 1, "to") && tokens.len() == column + 3
+    {
+        Alteration::RenameColumn {
+            from: literal(column).ok_or_else(invalid)?,
+            to: literal(column + 2).ok_or_else(invalid)?,
+        }
+    } else {
+        return Err(invalid());
+    };
+    Ok(Statement::AlterTable { table, alteration })
+}
+
+/// Parse a single table name
+fn parse_table_name(source: &str) -> CIndexResult<String> {
+    let tokens = raw_tokens(source);
+    if tokens.len() != 1 {
+        return Err(CIndexError::InvalidQueryStatement(format!(
+            "Statement requires a single table name but given \"{}\"",
+            source.trim()
+        )));
+    }
+    Ok(unquote(&tokens[0]))
+}
+
+/// Parse "ON table (column)" of index statements
+fn parse_index_target(source: &str) -> CIndexResult<(String, String)> {
+    let invalid = || {
+        CIndexError::InvalidQueryStatement(format!(
+            "Index statement should be \"ON table (column)\" but given \"{}\"",
+            source.trim()
+        ))
+    };
+    let mut tokens = raw_tokens(strip_keyword(source, "on").ok_or_else(invalid)?).into_iter();
+    let table = unquote(&tokens.next().ok_or_else(invalid)?);
+    let columns = list_items(&tokens.collect::<Vec<_>>().join(" "));
+    if columns.len() != 1 {
+        return Err(invalid());
+    }
+    Ok((table, unquote(&columns[0])))
+}
+
+/// Parse index type of "USING" clause
+fn parse_index_type(source: &str) -> CIndexResult<IndexType> {
+    match source.trim().to_lowercase().as_str() {
+        "hash" => Ok(IndexType::Hash),
+        "btree" | "ordered" => Ok(IndexType::Ordered),
+        _ => Err(CIndexError::InvalidQueryStatement(format!(
+            "Index type can only be HASH or BTREE but given \"{}\"",
+            source.trim()
+        ))),
+    }
+}
+
+/// Split source into whitespace separated tokens
+///
+/// Quotes and escape characters are preserved so that a caller can tell whether a token was
+/// quoted or not. Use unquote to get a literal value of a token.
+fn raw_tokens(source: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut on_quote = false;
+    let mut chunk = String::new();
+    let mut iter = source.chars();
+    while let Some(ch) = iter.next() {
+        match ch {
+            // Escaped character is always a part of the chunk
+            '\\' => {
+                chunk.push(ch);
+                if let Some(next) = iter.next() {
+                    chunk.push(next);
+                }
+                continue;
+            }
+            '\'' => on_quote = !on_quote,
+            _ if ch.is_whitespace() && !on_quote => {
+                if !chunk.is_empty() {
+                    tokens.push(std::mem::take(&mut chunk));
                 }
+                continue;
             }
-            _ => previous = ch,
+            _ => (),
         }
         chunk.push(ch);
     }
@@ -323,6 +780,97 @@ fn tokens_with_quote(source: &str) -> Vec<String> {
     tokens
 }
 
+/// Words which should be quoted to be used as a literal value
+const RESERVED: [&str; 14] = [
+    "select", "from", "where", "join", "hmap", "order", "by", "limit", "offset", "flag", "explain",
+    "and", "or", "in",
+];
+
+/// Quote a literal value so that it is parsed back as a single token
+///
+/// Value is returned as it is if no quotation is required.
+pub(crate) fn quote(value: &str) -> Cow<'_, str> {
+    let requires_quote = value.is_empty()
+        || value.starts_with(':')
+        || value == "?"
+        || RESERVED.contains(&value.to_lowercase().as_str())
+        || value
+            .chars()
+            .any(|ch| ch.is_whitespace() || matches!(ch, '\'' | '\\' | ',' | '(' | ')'));
+
+    if !requires_quote {
+        return Cow::Borrowed(value);
+    }
+
+    let mut quoted = String::from("'");
+    for ch in value.chars() {
+        if ch == '\'' || ch == '\\' {
+            quoted.push('\\');
+        }
+        quoted.push(ch);
+    }
+    quoted.push('\'');
+    Cow::Owned(quoted)
+}
+
+/// Check if a raw token contains quote or escape characters
+fn is_quoted(token: &str) -> bool {
+    token.contains('\'') || token.contains('\\')
+}
+
+/// Strip surrounding quotes and escape characters from a raw token
+fn unquote(token: &str) -> String {
+    let mut literal = String::new();
+    let mut iter = token.chars();
+    while let Some(ch) = iter.next() {
+        match ch {
+            '\\' => {
+                if let Some(next) = iter.next() {
+                    literal.push(next);
+                }
+            }
+            '\'' => (),
+            _ => literal.push(ch),
+        }
+    }
+    literal
+}
+
+/// Split a raw token of list syntax into raw items
+///
+/// Parenthesis and commas inside quotes are preserved, e.g. "('a,b'," yields "'a,b'"
+fn list_items(token: &str) -> Vec<String> {
+    let mut items = vec![];
+    let mut on_quote = false;
+    let mut chunk = String::new();
+    let mut iter = token.chars();
+    while let Some(ch) = iter.next() {
+        match ch {
+            '\\' => {
+                chunk.push(ch);
+                if let Some(next) = iter.next() {
+                    chunk.push(next);
+                }
+                continue;
+            }
+            '\'' => on_quote = !on_quote,
+            '(' | ')' if !on_quote => continue,
+            ',' if !on_quote => {
+                if !chunk.is_empty() {
+                    items.push(std::mem::take(&mut chunk));
+                }
+                continue;
+            }
+            _ => (),
+        }
+        chunk.push(ch);
+    }
+    if !chunk.is_empty() {
+        items.push(chunk);
+    }
+    items
+}
+
 // // TODO
 // // This should be relocated to proper module
 // #[derive(Default)]
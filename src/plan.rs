@@ -0,0 +1,218 @@
+use crate::index::IndexType;
+use crate::models::OrderType;
+use crate::query::{Operator, Predicate, Query};
+use crate::table::Table;
+use crate::CIndexResult;
+use std::fmt::Display;
+
+/// Execution plan of a query
+///
+/// Plan can be printed as text or inspected as a structured value.
+#[derive(Debug, Clone)]
+pub struct QueryPlan {
+    pub table: String,
+    pub total_rows: usize,
+    /// Filters in order of evaluation
+    pub filters: Vec<FilterStep>,
+    pub sort: Option<SortStep>,
+    pub projection: Vec<String>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+    pub estimated_rows: usize,
+}
+
+/// Single predicate evaluation of a plan
+#[derive(Debug, Clone)]
+pub struct FilterStep {
+    pub column: String,
+    pub operator: Operator,
+    pub arguments: Vec<String>,
+    pub access: AccessPath,
+    /// Estimated rows remaining after this filter
+    pub estimated_rows: usize,
+}
+
+/// How rows are accessed for a filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessPath {
+    /// Check every row
+    Scan,
+    /// Check rows which qualified previous filters
+    Filter,
+    /// Look up rows from a hash index
+    HashIndex,
+    /// Scan a range of an ordered index
+    OrderedIndex,
+}
+
+/// Sort step of a plan
+#[derive(Debug, Clone)]
+pub struct SortStep {
+    pub column: String,
+    pub descending: bool,
+    /// Rows are read in order from an ordered index instead of sorting
+    pub indexed: bool,
+}
+
+impl QueryPlan {
+    /// Create a plan of query for given table
+    pub(crate) fn new(table_name: &str, table: &Table, query: &Query) -> CIndexResult<Self> {
+        table.validate(query)?;
+
+        let total_rows = table.row_count();
+        let mut estimated_rows = total_rows;
+        let mut filters = vec![];
+        let predicates = query.predicates.as_deref().unwrap_or(&[]);
+
+        // Indexed predicate is evaluated first
+        let indexed = table.find_index(predicates);
+        if let Some((position, index)) = indexed {
+            let pre = &predicates[position];
+            estimated_rows = table.lookup(index, pre)?.len();
+            let access = match index.index_type() {
+                IndexType::Hash => AccessPath::HashIndex,
+                IndexType::Ordered => AccessPath::OrderedIndex,
+            };
+            filters.push(FilterStep::new(pre, access, estimated_rows));
+        }
+
+        // Other predicates are evaluated from the most selective one, with LIKE last
+        let resolved = predicates
+            .iter()
+            .map(|pre| table.resolve(pre))
+            .collect::<CIndexResult<Vec<_>>>()?;
+        // No row qualifies a predicate which is out of column's range
+        let unsatisfiable = resolved.iter().any(|pre| table.excludes(pre));
+        let mut resolved = resolved
+            .into_iter()
+            .enumerate()
+            .filter(|(position, _)| !matches!(indexed, Some((p, _)) if p == *position))
+            .map(|(_, pre)| pre)
+            .collect::<Vec<_>>();
+        table.order_predicates(&mut resolved);
+        for pre in &resolved {
+            estimated_rows = (estimated_rows as f64 * table.selectivity(pre)).ceil() as usize;
+            let access = if filters.is_empty() {
+                AccessPath::Scan
+            } else {
+                AccessPath::Filter
+            };
+            filters.push(FilterStep::new(pre.predicate, access, estimated_rows));
+        }
+        if unsatisfiable {
+            estimated_rows = 0;
+            for step in &mut filters {
+                step.estimated_rows = 0;
+            }
+        }
+
+        // Ordered index replaces sorting only when rows are not from another index
+        let sort_indexed = indexed.is_none() && table.find_order_index(query).is_some();
+        let sort = match &query.order_type {
+            OrderType::Asec(col) => Some(SortStep {
+                column: col.clone(),
+                descending: false,
+                indexed: sort_indexed,
+            }),
+            OrderType::Desc(col) => Some(SortStep {
+                column: col.clone(),
+                descending: true,
+                indexed: sort_indexed,
+            }),
+            OrderType::None => None,
+        };
+
+        let mut all_column = false;
+        let mut projection = vec![];
+        let mut supplement = vec![];
+        for col in &query.column_names {
+            if col == "*" {
+                all_column = true;
+            } else if table.header.contains(col) {
+                projection.push(col.clone());
+            } else {
+                supplement.push(col.clone());
+            }
+        }
+        if all_column {
+            projection = table.column_names().iter().map(|c| c.to_string()).collect();
+        }
+        projection.extend(supplement);
+
+        let (offset, limit) = query.range;
+        let limit = if limit == 0 { None } else { Some(limit) };
+        estimated_rows = estimated_rows.saturating_sub(offset);
+        if let Some(limit) = limit {
+            estimated_rows = estimated_rows.min(limit);
+        }
+
+        Ok(Self {
+            table: table_name.to_owned(),
+            total_rows,
+            filters,
+            sort,
+            projection,
+            offset,
+            limit,
+            estimated_rows,
+        })
+    }
+}
+
+impl FilterStep {
+    fn new(predicate: &Predicate, access: AccessPath, estimated_rows: usize) -> Self {
+        Self {
+            column: predicate.column.clone(),
+            operator: predicate.operation,
+            arguments: predicate.arguments.clone(),
+            access,
+            estimated_rows,
+        }
+    }
+}
+
+impl Display for AccessPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Scan => write!(f, "full scan"),
+            Self::Filter => write!(f, "filter"),
+            Self::HashIndex => write!(f, "hash index"),
+            Self::OrderedIndex => write!(f, "ordered index"),
+        }
+    }
+}
+
+impl Display for QueryPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Table   \"{}\" ({} rows)", self.table, self.total_rows)?;
+        for step in &self.filters {
+            let arguments = match step.operator {
+                Operator::Between => step.arguments.join(" AND "),
+                Operator::In => format!("({})", step.arguments.join(", ")),
+                _ => step.arguments.join(" "),
+            };
+            writeln!(
+                f,
+                "Filter  {} {} {} [{}] ~{} rows",
+                step.column, step.operator, arguments, step.access, step.estimated_rows
+            )?;
+        }
+        if let Some(sort) = &self.sort {
+            writeln!(
+                f,
+                "Sort    {} {}{}",
+                sort.column,
+                if sort.descending { "DESC" } else { "ASEC" },
+                if sort.indexed { " [ordered index]" } else { "" }
+            )?;
+        }
+        writeln!(f, "Project {}", self.projection.join(","))?;
+        if self.offset != 0 {
+            writeln!(f, "Offset  {}", self.offset)?;
+        }
+        if let Some(limit) = self.limit {
+            writeln!(f, "Limit   {}", limit)?;
+        }
+        write!(f, "Estimated rows : {}", self.estimated_rows)
+    }
+}
@@ -1,11 +1,15 @@
 use crate::error::{CIndexError, CIndexResult};
 use crate::models::OrderType;
-use crate::parser::Parser;
+use crate::parser::{quote, Parser};
 use regex::Regex;
-use std::collections::HashSet;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 
 /// Query to index a table
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Query {
     pub table_name: String,
     pub column_names: Vec<String>,
@@ -30,6 +34,57 @@ impl std::str::FromStr for Query {
     }
 }
 
+/// Canonical raw query which can be parsed back into an equivalent query
+///
+/// Column names which include comma cannot be represented.
+impl std::fmt::Display for Query {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "SELECT {} FROM {}",
+            quote(&self.column_names.join(",")),
+            quote(&self.table_name)
+        )?;
+
+        let predicates = self.predicates.iter().flatten();
+        for (index, pre) in predicates.enumerate() {
+            if index == 0 {
+                write!(f, " WHERE")?;
+            } else {
+                match pre.separator {
+                    Separator::And => write!(f, " AND")?,
+                    Separator::Or => write!(f, " OR")?,
+                }
+            }
+            write!(f, " {}", pre)?;
+        }
+
+        match &self.order_type {
+            OrderType::Asec(col) => write!(f, " ORDER BY {} ASEC", quote(col))?,
+            OrderType::Desc(col) => write!(f, " ORDER BY {} DESC", quote(col))?,
+            OrderType::None => (),
+        }
+        if self.range.0 != 0 {
+            write!(f, " OFFSET {}", self.range.0)?;
+        }
+        if self.range.1 != 0 {
+            write!(f, " LIMIT {}", self.range.1)?;
+        }
+        if let Some(map) = &self.column_map {
+            write!(f, " HMAP {}", quote(&map.join(",")))?;
+        }
+        if !self.flags.flags.is_empty() {
+            write!(f, " FLAG")?;
+            for flag in [QueryFlagType::Phd, QueryFlagType::Sup, QueryFlagType::TP] {
+                if self.flags.contains(flag) {
+                    write!(f, " {}", flag)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
 impl Query {
     /// Build an empty query
     pub fn build() -> Self {
@@ -67,6 +122,36 @@ impl Query {
         self
     }
 
+    /// Create a new query with placeholders replaced by given parameters
+    ///
+    /// Parameters are bound as literal values and never parsed as a part of query statement.
+    pub fn bind(&self, params: &Params) -> CIndexResult<Self> {
+        let mut query = self.clone();
+        let mut used = 0;
+        if let Some(predicates) = query.predicates.as_mut() {
+            for predicate in predicates {
+                predicate.bind(params, &mut used)?;
+            }
+        }
+
+        if params.positional.len() > used {
+            return Err(CIndexError::InvalidQueryStatement(format!(
+                "Query has {} positional placeholders but given {} parameters",
+                used,
+                params.positional.len()
+            )));
+        }
+        Ok(query)
+    }
+
+    /// Check if query has placeholders without bound values
+    pub fn has_placeholders(&self) -> bool {
+        self.predicates
+            .iter()
+            .flatten()
+            .any(|pre| !pre.placeholders.is_empty())
+    }
+
     // This is ok to have too many arguments because it is inner usage only
     /// Create a query with every information
     #[allow(clippy::too_many_arguments)]
@@ -93,14 +178,74 @@ impl Query {
     }
 }
 
+/// Query which was parsed and validated once to be executed multiple times
+///
+/// Columns are resolved into positions once and reused while a table has the same columns.
+/// Create one with Indexer::prepare.
+#[derive(Debug, Clone)]
+pub struct PreparedQuery {
+    query: Query,
+    pub(crate) columns: ResolvedColumns,
+}
+
+impl PreparedQuery {
+    pub(crate) fn new(query: Query, columns: ResolvedColumns) -> Self {
+        Self { query, columns }
+    }
+
+    /// Get inner query
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// Get query with bound parameters
+    ///
+    /// This doesn't clone the query when it has no placeholders.
+    pub(crate) fn bind(&self, params: &Params) -> CIndexResult<Cow<'_, Query>> {
+        if self.query.has_placeholders() {
+            Ok(Cow::Owned(self.query.bind(params)?))
+        } else {
+            Ok(Cow::Borrowed(&self.query))
+        }
+    }
+}
+
+/// Positions of columns which a query refers to in a table
+#[derive(Debug, Clone)]
+pub(crate) struct ResolvedColumns {
+    /// Columns of the table in order, which another table should have to reuse positions
+    pub(crate) schema: Vec<String>,
+    /// Header record if query has a print header flag
+    pub(crate) header: Option<Vec<String>>,
+    /// Positions of target columns where None is a supplement column
+    pub(crate) targets: Vec<Option<usize>>,
+    /// Positions of predicates' columns in order of predicates
+    pub(crate) predicates: Vec<usize>,
+    pub(crate) order: Option<usize>,
+}
+
+impl ResolvedColumns {
+    /// Check if positions are valid for given columns of a table
+    pub(crate) fn matches(&self, columns: &[&str]) -> bool {
+        self.schema
+            .iter()
+            .map(|c| c.as_str())
+            .eq(columns.iter().copied())
+    }
+}
+
 /// Predicate to decide whether a specific row qualifies a query or not
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Predicate {
     pub(crate) separator: Separator,
     pub(crate) column: String,
     pub(crate) operation: Operator,
     pub(crate) arguments: Vec<String>,
+    #[cfg_attr(feature = "serde", serde(with = "regex_serde"))]
     pub(crate) matcher: Option<Regex>,
+    // Argument index and its placeholder
+    pub(crate) placeholders: Vec<(usize, Placeholder)>,
 }
 
 impl Predicate {
@@ -113,6 +258,7 @@ impl Predicate {
             operation: Operator::Equal,
             arguments: vec![],
             matcher: None,
+            placeholders: vec![],
         }
     }
 
@@ -162,6 +308,7 @@ impl Predicate {
             operation,
             arguments: vec![],
             matcher: None,
+            placeholders: vec![],
         }
     }
 
@@ -191,10 +338,168 @@ impl Predicate {
     pub fn add_arg(&mut self, arg: &str) {
         self.arguments.push(arg.to_owned());
     }
+
+    /// Append placeholder argument which is later replaced by a bound value
+    pub(crate) fn add_placeholder(&mut self, placeholder: Placeholder) {
+        self.placeholders
+            .push((self.arguments.len(), placeholder.clone()));
+        self.arguments.push(placeholder.to_string());
+    }
+
+    /// Replace placeholders with given parameters
+    fn bind(&mut self, params: &Params, used: &mut usize) -> CIndexResult<()> {
+        let placeholders = std::mem::take(&mut self.placeholders);
+        let bound = !placeholders.is_empty();
+        for (index, placeholder) in placeholders {
+            let value = match &placeholder {
+                Placeholder::Positional(pos) => {
+                    *used = (*used).max(pos + 1);
+                    params.positional.get(*pos)
+                }
+                Placeholder::Named(name) => params.named.get(name),
+            }
+            .ok_or_else(|| {
+                CIndexError::InvalidQueryStatement(format!(
+                    "No parameter was given for placeholder \"{}\"",
+                    placeholder
+                ))
+            })?;
+            self.arguments[index] = value.to_owned();
+        }
+
+        // Matcher is built again only from a bound pattern
+        if bound && self.operation == Operator::Like {
+            let pattern = self.arguments.first().cloned().ok_or_else(|| {
+                CIndexError::InvalidQueryStatement(format!(
+                    "Operator Like of column \"{}\" requires a pattern",
+                    self.column
+                ))
+            })?;
+            self.set_matcher(&pattern)?;
+        }
+        Ok(())
+    }
+
+    /// Check if predicate has a valid number of arguments for its operator
+    pub(crate) fn check_arguments(&self) -> CIndexResult<()> {
+        let valid = match self.operation {
+            Operator::Between => self.arguments.len() == 2,
+            Operator::In => !self.arguments.is_empty(),
+            _ => self.arguments.len() == 1,
+        };
+
+        if !valid {
+            let expected = match self.operation {
+                Operator::Between => "two arguments",
+                Operator::In => "at least one argument",
+                _ => "a single argument",
+            };
+            return Err(CIndexError::InvalidQueryStatement(format!(
+                "Operator {:?} of column \"{}\" requires {} but given {:?}",
+                self.operation, self.column, expected, self.arguments
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Placeholder argument of a raw query
+///
+/// - "?" is a positional placeholder which is numbered in order of appearance
+/// - ":name" is a named placeholder
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) enum Placeholder {
+    Positional(usize),
+    Named(String),
+}
+
+impl Placeholder {
+    /// Create placeholder from an unquoted token
+    pub(crate) fn from_token(token: &str, positional: &mut usize) -> Option<Self> {
+        if token == "?" {
+            *positional += 1;
+            return Some(Self::Positional(*positional - 1));
+        }
+
+        let name = token.strip_prefix(':')?;
+        if !name.is_empty() && name.chars().all(|ch| ch.is_alphanumeric() || ch == '_') {
+            Some(Self::Named(name.to_owned()))
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for Placeholder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Positional(_) => write!(f, "?"),
+            Self::Named(name) => write!(f, ":{}", name),
+        }
+    }
+}
+
+/// Parameters to bind into placeholders of a query
+#[derive(Debug, Default, Clone)]
+pub struct Params {
+    positional: Vec<String>,
+    named: HashMap<String, String>,
+}
+
+impl Params {
+    /// Create empty parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a value for the next positional placeholder "?"
+    pub fn arg(mut self, value: impl ToString) -> Self {
+        self.positional.push(value.to_string());
+        self
+    }
+
+    /// Set a value for a named placeholder ":name"
+    pub fn named(mut self, name: &str, value: impl ToString) -> Self {
+        self.named
+            .insert(name.trim_start_matches(':').to_owned(), value.to_string());
+        self
+    }
+}
+
+impl std::fmt::Display for Predicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let arguments = self
+            .arguments
+            .iter()
+            .enumerate()
+            .map(|(index, arg)| {
+                // Placeholder is written as it is
+                if self.placeholders.iter().any(|(i, _)| *i == index) {
+                    Cow::Borrowed(arg.as_str())
+                } else {
+                    quote(arg)
+                }
+            })
+            .collect::<Vec<_>>();
+        let arguments = match self.operation {
+            Operator::Between => arguments.join(" AND "),
+            Operator::In => format!("({})", arguments.join(", ")),
+            _ => arguments.join(" "),
+        };
+        write!(
+            f,
+            "{} {} {}",
+            quote(&self.column),
+            self.operation,
+            arguments
+        )
+    }
 }
 
 /// Operator to calculate operands
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Operator {
     Bigger,
     BiggerOrEqual,
@@ -230,24 +535,55 @@ impl Operator {
     }
 }
 
-#[derive(Debug)]
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let token = match self {
+            Self::Bigger => ">",
+            Self::BiggerOrEqual => ">=",
+            Self::Smaller => "<",
+            Self::SmallerOrEqual => "<=",
+            Self::Equal => "=",
+            Self::NotEqual => "!=",
+            Self::Between => "BETWEEN",
+            Self::In => "IN",
+            Self::Like => "LIKE",
+        };
+        write!(f, "{}", token)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Separator {
     And,
     Or,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct QueryFlags {
     flags: HashSet<QueryFlagType>,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum QueryFlagType {
     Phd, // Print header
     Sup, // Supplement
     TP,  // Tranpose
 }
 
+impl std::fmt::Display for QueryFlagType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let flag = match self {
+            Self::Phd => "PHD",
+            Self::Sup => "SUP",
+            Self::TP => "TP",
+        };
+        write!(f, "{}", flag)
+    }
+}
+
 impl Default for QueryFlags {
     fn default() -> Self {
         Self::new()
@@ -283,3 +619,20 @@ impl QueryFlags {
         Ok(())
     }
 }
+
+/// Serialize regex matcher as its pattern
+#[cfg(feature = "serde")]
+mod regex_serde {
+    use regex::Regex;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(matcher: &Option<Regex>, s: S) -> Result<S::Ok, S::Error> {
+        matcher.as_ref().map(|m| m.as_str()).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Regex>, D::Error> {
+        Option::<String>::deserialize(d)?
+            .map(|pattern| Regex::new(&pattern).map_err(D::Error::custom))
+            .transpose()
+    }
+}
@@ -0,0 +1,108 @@
+use crate::index::IndexType;
+use crate::query::Query;
+use dcsv::Value;
+
+/// Statement which can be executed by an indexer
+#[derive(Debug)]
+pub(crate) enum Statement {
+    Select(Query),
+    Explain(Query),
+    CreateIndex {
+        table: String,
+        column: String,
+        index_type: IndexType,
+    },
+    DropIndex {
+        table: String,
+        column: String,
+    },
+    Insert {
+        table: String,
+        // Empty columns mean every column of the table
+        columns: Vec<String>,
+        source: InsertSource,
+        on_conflict: Option<Conflict>,
+    },
+    Update {
+        assignments: Vec<Assignment>,
+        // Query of every column which has predicates of the statement
+        query: Query,
+    },
+    // Query of every column which has predicates of rows to delete
+    Delete(Query),
+    CreateTable {
+        table: String,
+        query: Query,
+    },
+    AlterTable {
+        table: String,
+        alteration: Alteration,
+    },
+    Begin,
+    Commit,
+    Rollback,
+    Undo(String),
+    Redo(String),
+}
+
+/// Rows to insert
+#[derive(Debug)]
+pub(crate) enum InsertSource {
+    Values(Vec<Vec<String>>),
+    Select(Query),
+}
+
+/// Resolution of an inserted row whose primary key already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Conflict {
+    Nothing,
+    Update,
+}
+
+/// Change of a table schema
+#[derive(Debug)]
+pub(crate) enum Alteration {
+    AddColumn { column: String, default: String },
+    DropColumn(String),
+    RenameColumn { from: String, to: String },
+    RenameTable(String),
+}
+
+/// Assignment of an update statement
+#[derive(Debug)]
+pub(crate) struct Assignment {
+    pub column: String,
+    pub expression: Expression,
+}
+
+/// Value of an assignment
+///
+/// Functions take a column whose value of a same row is used.
+#[derive(Debug)]
+pub(crate) enum Expression {
+    Literal(String),
+    Upper(String),
+    Lower(String),
+    Trim(String),
+}
+
+impl Expression {
+    /// Get a column which the expression reads
+    pub fn column(&self) -> Option<&str> {
+        match self {
+            Self::Literal(_) => None,
+            Self::Upper(column) | Self::Lower(column) | Self::Trim(column) => Some(column),
+        }
+    }
+
+    /// Evaluate the expression with a value of its column
+    pub fn evaluate(&self, source: Option<&Value>) -> String {
+        let source = source.map(|v| v.to_string()).unwrap_or_default();
+        match self {
+            Self::Literal(literal) => literal.to_owned(),
+            Self::Upper(_) => source.to_uppercase(),
+            Self::Lower(_) => source.to_lowercase(),
+            Self::Trim(_) => source.trim().to_owned(),
+        }
+    }
+}
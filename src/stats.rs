@@ -0,0 +1,106 @@
+use crate::query::Operator;
+use dcsv::Value;
+
+/// Statistics of a column which are used to order predicates
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ColumnStats {
+    pub(crate) distinct: usize,
+    pub(crate) min: Option<Value>,
+    pub(crate) max: Option<Value>,
+}
+
+impl ColumnStats {
+    /// Collect statistics from distinct values of a column
+    pub fn from_distinct<'a>(values: impl Iterator<Item = &'a Value>) -> Self {
+        let mut stats = Self::default();
+        for value in values {
+            stats.distinct += 1;
+            if stats.min.as_ref().map(|min| value < min).unwrap_or(true) {
+                stats.min.replace(value.clone());
+            }
+            if stats.max.as_ref().map(|max| value > max).unwrap_or(true) {
+                stats.max.replace(value.clone());
+            }
+        }
+        stats
+    }
+
+    /// Estimate a ratio of rows which qualify an operator with typed arguments
+    pub fn selectivity(&self, operator: Operator, arguments: &[Value]) -> f64 {
+        if self.distinct == 0 {
+            return default_selectivity(operator, arguments.len());
+        }
+        let distinct = self.distinct as f64;
+        match operator {
+            Operator::Equal => 1.0 / distinct,
+            Operator::NotEqual => 1.0 - 1.0 / distinct,
+            Operator::In => (arguments.len() as f64 / distinct).min(1.0),
+            Operator::Bigger | Operator::BiggerOrEqual => self
+                .fraction(&arguments[0])
+                .map(|f| 1.0 - f)
+                .unwrap_or_else(|| default_selectivity(operator, arguments.len())),
+            Operator::Smaller | Operator::SmallerOrEqual => self
+                .fraction(&arguments[0])
+                .unwrap_or_else(|| default_selectivity(operator, arguments.len())),
+            Operator::Between => match (self.fraction(&arguments[0]), self.fraction(&arguments[1]))
+            {
+                (Some(min), Some(max)) => (max - min).max(0.0),
+                _ => default_selectivity(operator, arguments.len()),
+            },
+            Operator::Like => default_selectivity(operator, arguments.len()),
+        }
+    }
+
+    /// Check if no value within min and max can qualify an operator with typed arguments
+    pub fn excludes(&self, operator: Operator, arguments: &[Value]) -> bool {
+        let (min, max) = match (&self.min, &self.max) {
+            (Some(min), Some(max)) => (min, max),
+            _ => return false,
+        };
+        let outside = |arg: &Value| arg < min || arg > max;
+        match operator {
+            Operator::Equal => outside(&arguments[0]),
+            Operator::In => arguments.iter().all(outside),
+            Operator::NotEqual => min == max && &arguments[0] == min,
+            Operator::Bigger => &arguments[0] >= max,
+            Operator::BiggerOrEqual => &arguments[0] > max,
+            Operator::Smaller => &arguments[0] <= min,
+            Operator::SmallerOrEqual => &arguments[0] < min,
+            Operator::Between => {
+                &arguments[0] > max || &arguments[1] < min || arguments[0] > arguments[1]
+            }
+            Operator::Like => false,
+        }
+    }
+
+    /// Get a position of a number between min and max as a ratio
+    ///
+    /// Text has no meaningful distance, therefore this returns none.
+    fn fraction(&self, value: &Value) -> Option<f64> {
+        match (&self.min, &self.max, value) {
+            (Some(Value::Number(min)), Some(Value::Number(max)), Value::Number(value)) => {
+                if max == min {
+                    return Some(if value < min { 0.0 } else { 1.0 });
+                }
+                let ratio = (*value as f64 - *min as f64) / (*max as f64 - *min as f64);
+                Some(ratio.clamp(0.0, 1.0))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Rough ratio of rows which qualify an operator without statistics
+pub(crate) fn default_selectivity(operator: Operator, argument_count: usize) -> f64 {
+    match operator {
+        Operator::Equal => 0.1,
+        Operator::NotEqual => 0.9,
+        Operator::In => (argument_count as f64 * 0.1).min(1.0),
+        Operator::Between => 0.25,
+        Operator::Like => 0.5,
+        Operator::Bigger
+        | Operator::BiggerOrEqual
+        | Operator::Smaller
+        | Operator::SmallerOrEqual => 0.33,
+    }
+}
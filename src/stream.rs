@@ -0,0 +1,59 @@
+use crate::{CIndexError, CIndexResult};
+use dcsv::utils::csv_row_to_vector;
+use std::io::BufRead;
+
+/// Reader which yields csv records one by one from a stream
+///
+/// This follows the same reader options with a default table, which consumes double quotes and
+/// ignores empty rows. A record can span multiple lines inside double quotes.
+pub(crate) struct RecordReader<R: BufRead> {
+    input: R,
+    line_number: usize,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    pub fn new(input: R) -> Self {
+        Self {
+            input,
+            line_number: 0,
+        }
+    }
+
+    /// Read a next record
+    ///
+    /// This returns none when the stream has reached its end.
+    pub fn next_record(&mut self) -> CIndexResult<Option<Vec<String>>> {
+        let mut chunk = String::new();
+        loop {
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                if chunk.is_empty() {
+                    return Ok(None);
+                }
+                return Err(CIndexError::InvalidTableInput(format!(
+                    "Record of line \"{}\" has unterminated double quote",
+                    self.line_number
+                )));
+            }
+            self.line_number += 1;
+            chunk.push_str(&line);
+
+            // Odd number of double quotes means a newline inside a quoted value
+            if chunk.matches('"').count() % 2 == 1 {
+                continue;
+            }
+
+            let record = chunk.trim_end_matches(['\n', '\r']);
+            if record.trim().is_empty() {
+                chunk.clear();
+                continue;
+            }
+            return Ok(Some(csv_row_to_vector(record, None, true)));
+        }
+    }
+
+    /// Get a line number of a lastly read record
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+}
@@ -1,22 +1,207 @@
+use crate::columnar::ColumnarData;
+use crate::constraint::{Constraint, Violation};
 use crate::error::{CIndexError, CIndexResult};
+use crate::index::{ColumnIndex, IndexType, OrderedIndex, UniqueKey};
+#[cfg(feature = "mmap")]
+use crate::mapped::MappedData;
 use crate::models::OrderType;
-use crate::query::Query;
+use crate::query::{Query, QueryFlagType, ResolvedColumns};
+use crate::statement::Assignment;
+use crate::stats::{default_selectivity, ColumnStats};
 use crate::ReaderOption;
 use crate::{Operator, Predicate};
-use dcsv::{Reader, Row, VirtualData};
+use dcsv::{Reader, Value, ValueType, VirtualData};
 #[cfg(feature = "rayon")]
 use rayon::prelude::*;
-use std::collections::HashSet;
+use std::cmp::Ordering;
+use std::collections::hash_map::Entry;
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::fmt::Display;
-use std::io::BufRead;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
 use std::iter::FromIterator;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
 
+#[derive(Clone)]
 pub(crate) struct Table {
     pub(crate) header: HashSet<String>,
-    pub(crate) data: VirtualData,
+    pub(crate) storage: Storage,
+    // Column name and its index
+    pub(crate) indexes: HashMap<String, Arc<ColumnIndex>>,
+    pub(crate) source: Option<TableSource>,
+    // Statistics in order of columns, which is empty for a mapped table
+    pub(crate) stats: Vec<ColumnStats>,
+    pub(crate) constraints: Vec<Constraint>,
+    // Keys of unique constraints in order of the constraints
+    pub(crate) unique_keys: Vec<UniqueKey>,
+    pub(crate) primary_key: Option<UniqueKey>,
+}
+
+/// Rows of a table
+#[derive(Clone)]
+pub(crate) enum Storage {
+    /// Every row is parsed and held in memory by columns
+    ///
+    /// Rows are shared between snapshots of a table and copied when one of them is modified.
+    Memory(Arc<ColumnarData>),
+    /// Rows are decoded from a memory mapped file on demand
+    ///
+    /// Mapped file is shared between snapshots of a table.
+    #[cfg(feature = "mmap")]
+    Mapped(Arc<MappedData>),
+}
+
+/// Row of a table which is borrowed from memory or decoded from a mapped file
+pub(crate) enum Record<'a> {
+    Memory {
+        data: &'a ColumnarData,
+        position: usize,
+    },
+    #[cfg(feature = "mmap")]
+    Mapped(Vec<Value>),
+}
+
+impl<'a> Record<'a> {
+    /// Get a cell value of a column position
+    pub fn get(&self, column: usize) -> &Value {
+        match self {
+            Self::Memory { data, position } => data.value(column, *position),
+            #[cfg(feature = "mmap")]
+            Self::Mapped(values) => &values[column],
+        }
+    }
+
+    /// Get values of target positions
+    ///
+    /// None position is a supplement column which is always empty.
+    pub fn project(&self, positions: &[Option<usize>]) -> Vec<String> {
+        positions
+            .iter()
+            .map(|position| match position {
+                Some(position) => self.get(*position).to_string(),
+                None => String::new(),
+            })
+            .collect()
+    }
+}
+
+/// Predicate of which column is resolved into a position and arguments are typed
+pub(crate) struct ResolvedPredicate<'a> {
+    pub(crate) column: usize,
+    pub(crate) predicate: &'a Predicate,
+    pub(crate) arguments: Vec<Value>,
+}
+
+impl<'a> ResolvedPredicate<'a> {
+    /// Check if a value qualifies the predicate
+    pub fn matches(&self, value: &Value) -> bool {
+        operate_value(value, &self.arguments, self.predicate)
+    }
+}
+
+/// Source file of a table which is used to validate a cached table
+#[derive(Clone)]
+pub(crate) struct TableSource {
+    pub(crate) path: PathBuf,
+    pub(crate) len: u64,
+    // Seconds and nanoseconds since unix epoch
+    pub(crate) modified: (u64, u32),
+}
+
+impl TableSource {
+    pub fn from_path(path: &Path) -> CIndexResult<Self> {
+        let metadata = std::fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Self {
+            path: path.to_owned(),
+            len: metadata.len(),
+            modified: (modified.as_secs(), modified.subsec_nanos()),
+        })
+    }
+
+    /// Check if source file has changed since it was read
+    pub fn is_stale(&self) -> bool {
+        match Self::from_path(&self.path) {
+            Ok(current) => current.len != self.len || current.modified != self.modified,
+            Err(_) => true,
+        }
+    }
 }
 
 impl Table {
+    pub(crate) fn from_data(data: ColumnarData) -> Self {
+        Self {
+            header: HashSet::from_iter(data.columns.iter().map(|c| c.name.clone())),
+            stats: vec![],
+            constraints: vec![],
+            unique_keys: vec![],
+            primary_key: None,
+            storage: Storage::Memory(Arc::new(data)),
+            indexes: HashMap::new(),
+            source: None,
+        }
+    }
+
+    /// Read a table from a file and remember the file as its source
+    pub fn from_path(path: &Path) -> CIndexResult<Self> {
+        let source = TableSource::from_path(path)?;
+        let mut table = Self::new(BufReader::new(File::open(path)?))?;
+        table.source.replace(source);
+        Ok(table)
+    }
+
+    /// Map a table from a file and remember the file as its source
+    ///
+    /// Every column of a mapped table is text.
+    #[cfg(feature = "mmap")]
+    pub fn from_mapped(path: &Path) -> CIndexResult<Self> {
+        let source = TableSource::from_path(path)?;
+        let data = MappedData::open(path)?;
+        Ok(Self {
+            header: HashSet::from_iter(data.columns.iter().cloned()),
+            storage: Storage::Mapped(Arc::new(data)),
+            indexes: HashMap::new(),
+            source: Some(source),
+            stats: vec![],
+            constraints: vec![],
+            unique_keys: vec![],
+            primary_key: None,
+        })
+    }
+
+    /// Read a table from its source file again while keeping index definitions
+    pub(crate) fn reload(&self) -> CIndexResult<Self> {
+        let path = match &self.source {
+            Some(source) => &source.path,
+            None => {
+                return Err(CIndexError::InvalidTableInput(
+                    "Table without a source file cannot be reloaded".to_owned(),
+                ))
+            }
+        };
+        let mut table = match &self.storage {
+            Storage::Memory(_) => Self::from_path(path)?,
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(_) => Self::from_mapped(path)?,
+        };
+        for (column, index) in &self.indexes {
+            table.create_index(column, index.index_type())?;
+        }
+        table.set_constraints(self.constraints.clone())?;
+        table.primary_key = self.primary_key.clone();
+        table.build_key()?;
+        Ok(table)
+    }
+
+    fn from_virtual(data: VirtualData) -> Self {
+        Self::from_data(ColumnarData::from_virtual(data))
+    }
+
     pub fn build(table_content: impl BufRead, reader_option: ReaderOption) -> CIndexResult<Self> {
         let data = Reader::new()
             .with_option(reader_option)
@@ -28,10 +213,7 @@ impl Table {
                 ))
             })?;
 
-        Ok(Self {
-            header: HashSet::from_iter(data.columns.iter().map(|c| c.name.clone())),
-            data,
-        })
+        Ok(Self::from_virtual(data))
     }
 
     pub fn new(table_content: impl BufRead) -> CIndexResult<Self> {
@@ -47,10 +229,7 @@ impl Table {
                 ))
             })?;
 
-        Ok(Self {
-            header: HashSet::from_iter(data.columns.iter().map(|c| c.name.clone())),
-            data,
-        })
+        Ok(Self::from_virtual(data))
     }
 
     pub fn new_with_headers(table_content: impl BufRead, headers: &[String]) -> CIndexResult<Self> {
@@ -66,118 +245,1452 @@ impl Table {
                 ))
             })?;
 
-        Ok(Self {
-            header: HashSet::from_iter(data.columns.iter().map(|c| c.name.clone())),
-            data,
+        Ok(Self::from_virtual(data))
+    }
+
+    /// Get the number of rows
+    pub fn row_count(&self) -> usize {
+        match &self.storage {
+            Storage::Memory(data) => data.len(),
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(data) => data.len(),
+        }
+    }
+
+    /// Get column names in order
+    pub fn column_names(&self) -> Vec<&str> {
+        match &self.storage {
+            Storage::Memory(data) => data.columns.iter().map(|c| c.name.as_str()).collect(),
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(data) => data.columns.iter().map(|c| c.as_str()).collect(),
+        }
+    }
+
+    /// Get a type of column
+    ///
+    /// Non-existent column is treated as text.
+    pub fn column_type(&self, column: &str) -> ValueType {
+        match &self.storage {
+            Storage::Memory(data) => data
+                .columns
+                .iter()
+                .find(|c| c.name == column)
+                .map(|c| c.column_type)
+                .unwrap_or(ValueType::Text),
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(_) => ValueType::Text,
+        }
+    }
+
+    /// Get a row of position
+    pub fn record(&self, position: usize) -> Record<'_> {
+        match &self.storage {
+            Storage::Memory(data) => Record::Memory { data, position },
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(data) => Record::Mapped(data.record(position)),
+        }
+    }
+
+    /// Get a position of column
+    pub fn column_position(&self, column: &str) -> CIndexResult<usize> {
+        let position = match &self.storage {
+            Storage::Memory(data) => data.column_position(column),
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(data) => data.columns.iter().position(|c| c == column),
+        };
+        position.ok_or_else(|| {
+            CIndexError::InvalidColumn(format!("Failed to get column \"{}\" from header", column))
         })
     }
 
-    pub(crate) fn query(&self, query: &Query) -> CIndexResult<Vec<&Row>> {
-        let boilerplate = vec![];
-        let predicates = if let Some(pre) = query.predicates.as_ref() {
-            for item in pre {
-                if !self.header.contains(&item.column) {
-                    return Err(CIndexError::InvalidColumn(format!(
-                        "Failed to get column \"{}\" from header",
-                        item.column
-                    )));
+    /// Get values of a column position in order of rows
+    pub fn column_values(&self, column: usize) -> impl Iterator<Item = Value> + '_ {
+        (0..self.row_count()).map(move |position| self.record(position).get(column).clone())
+    }
+
+    /// Resolve a predicate's column and type its arguments
+    pub(crate) fn resolve<'a>(
+        &self,
+        predicate: &'a Predicate,
+    ) -> CIndexResult<ResolvedPredicate<'a>> {
+        Ok(ResolvedPredicate {
+            column: self.column_position(&predicate.column)?,
+            predicate,
+            arguments: self.typed_arguments(predicate)?,
+        })
+    }
+
+    /// Create an index of a column
+    ///
+    /// This replaces an existing index of the column.
+    pub(crate) fn create_index(&mut self, column: &str, index_type: IndexType) -> CIndexResult<()> {
+        if !self.header.contains(column) {
+            return Err(CIndexError::InvalidColumn(format!(
+                "Cannot create index of non-existent column \"{}\"",
+                column
+            )));
+        }
+        self.indexes.insert(
+            column.to_owned(),
+            Arc::new(ColumnIndex::build(
+                self.column_values(self.column_position(column)?),
+                index_type,
+            )),
+        );
+        Ok(())
+    }
+
+    /// Drop an index of a column
+    pub(crate) fn drop_index(&mut self, column: &str) -> bool {
+        self.indexes.remove(column).is_some()
+    }
+
+    /// Check if rows can be modified
+    ///
+    /// Mapped table is read only because its rows are read from a file.
+    fn check_memory(&self) -> CIndexResult<()> {
+        match &self.storage {
+            Storage::Memory(_) => Ok(()),
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(_) => Err(CIndexError::InvalidTableInput(
+                "Mapped table cannot be modified".to_owned(),
+            )),
+        }
+    }
+
+    /// Get mutable in memory storage
+    ///
+    /// Rows which are shared with a snapshot are copied first, so this should be called only
+    /// when rows are written.
+    fn memory_mut(&mut self) -> CIndexResult<&mut ColumnarData> {
+        match &mut self.storage {
+            Storage::Memory(data) => Ok(Arc::make_mut(data)),
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(_) => Err(CIndexError::InvalidTableInput(
+                "Mapped table cannot be modified".to_owned(),
+            )),
+        }
+    }
+
+    /// Insert rows whose values are in order of given columns
+    ///
+    /// Every column is used if columns are empty, and columns which are not given get empty
+    /// values. Every row is validated before any row is inserted. This returns the number of
+    /// inserted rows.
+    pub(crate) fn insert_rows(
+        &mut self,
+        columns: &[String],
+        rows: Vec<Vec<String>>,
+    ) -> CIndexResult<usize> {
+        self.check_memory()?;
+        let typed_rows = self.typed_rows(columns, rows)?;
+        let start = self.row_count();
+        self.check_rows(
+            &typed_rows
+                .iter()
+                .enumerate()
+                .map(|(offset, values)| (start + offset, values.as_slice()))
+                .collect::<Vec<_>>(),
+        )?;
+        let count = typed_rows.len();
+        self.push_rows(typed_rows)?;
+        if count > 0 {
+            self.edited();
+        }
+        Ok(count)
+    }
+
+    /// Insert rows, or resolve rows whose primary key already exists
+    ///
+    /// Conflicting row replaces given columns of the existing row if update is true, otherwise
+    /// it is skipped. This returns the number of inserted and updated rows.
+    pub(crate) fn upsert_rows(
+        &mut self,
+        columns: &[String],
+        rows: Vec<Vec<String>>,
+        update: bool,
+    ) -> CIndexResult<usize> {
+        self.check_memory()?;
+        let key = self.primary_key.as_ref().ok_or_else(|| {
+            CIndexError::InvalidQueryStatement(
+                "Table without a primary key cannot resolve conflicts".to_owned(),
+            )
+        })?;
+        let given = if columns.is_empty() {
+            (0..self.header.len()).collect::<Vec<_>>()
+        } else {
+            columns
+                .iter()
+                .map(|c| self.column_position(c))
+                .collect::<CIndexResult<Vec<_>>>()?
+        };
+        let mut key_columns = vec![];
+        for column in &key.columns {
+            let position = self.column_position(column)?;
+            if !given.contains(&position) {
+                return Err(CIndexError::InvalidColumn(format!(
+                    "Primary key column \"{}\" requires a value",
+                    column
+                )));
+            }
+            key_columns.push(position);
+        }
+
+        // Rows to insert by their keys, where a later row replaces an earlier one
+        let mut inserts: Vec<Vec<Value>> = vec![];
+        let mut pending = HashMap::new();
+        let mut updates = HashMap::new();
+        for row in self.typed_rows(columns, rows)? {
+            let values = key_columns
+                .iter()
+                .map(|column| row[*column].to_string())
+                .collect::<Vec<_>>();
+            if let Some(position) = key.map.get(&values) {
+                if update {
+                    updates.insert(*position, row);
+                }
+            } else if let Some(index) = pending.get(&values) {
+                if update {
+                    inserts[*index] = row;
                 }
+            } else {
+                pending.insert(values, inserts.len());
+                inserts.push(row);
             }
-            pre
+        }
+        let count = updates.len() + inserts.len();
+
+        // Updated row keeps values of columns which are not given
+        let updates = updates
+            .into_iter()
+            .map(|(position, row)| {
+                let record = self.record(position);
+                let values = row
+                    .into_iter()
+                    .enumerate()
+                    .map(|(column, value)| {
+                        if given.contains(&column) {
+                            value
+                        } else {
+                            record.get(column).clone()
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                (position, values)
+            })
+            .collect::<Vec<_>>();
+        let start = self.row_count();
+        self.check_rows(
+            &updates
+                .iter()
+                .map(|(position, values)| (*position, values.as_slice()))
+                .chain(
+                    inserts
+                        .iter()
+                        .enumerate()
+                        .map(|(offset, values)| (start + offset, values.as_slice())),
+                )
+                .collect::<Vec<_>>(),
+        )?;
+
+        if !updates.is_empty() {
+            let positions = updates
+                .iter()
+                .map(|(position, _)| *position)
+                .collect::<Vec<_>>();
+            self.unmap_keys(&positions)?;
+            let data = self.memory_mut()?;
+            for column in &given {
+                data.set_values(
+                    *column,
+                    updates
+                        .iter()
+                        .map(|(position, row)| (*position, row[*column].clone()))
+                        .collect(),
+                );
+            }
+            self.map_keys(&positions)?;
+            let names = self
+                .column_names()
+                .iter()
+                .map(|c| c.to_string())
+                .collect::<Vec<_>>();
+            for column in &given {
+                let name = &names[*column];
+                if let Some(index_type) = self.indexes.get(name).map(|i| i.index_type()) {
+                    self.create_index(name, index_type)?;
+                }
+            }
+        }
+        self.push_rows(inserts)?;
+        if count > 0 {
+            self.edited();
+        }
+        Ok(count)
+    }
+
+    /// Convert rows of given columns into typed values of every column
+    fn typed_rows(
+        &self,
+        columns: &[String],
+        rows: Vec<Vec<String>>,
+    ) -> CIndexResult<Vec<Vec<Value>>> {
+        let names = self
+            .column_names()
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>();
+        let columns = if columns.is_empty() {
+            names.clone()
         } else {
-            &boilerplate // Empty predicates
+            columns.to_vec()
         };
 
-        // TODO
-        // Can it be improved?
-        #[cfg(feature = "rayon")]
-        let iter = self.data.rows.par_iter();
-        #[cfg(not(feature = "rayon"))]
-        let iter = self.data.rows.iter();
+        // Position of given value for each column of the table
+        let mut sources = vec![None; names.len()];
+        for (index, column) in columns.iter().enumerate() {
+            let position = self.column_position(column)?;
+            if sources[position].replace(index).is_some() {
+                return Err(CIndexError::InvalidColumn(format!(
+                    "Column \"{}\" is given more than once",
+                    column
+                )));
+            }
+        }
 
-        let mut queried: Vec<&Row> = iter.filter(|row| self.filter(row, predicates)).collect();
+        let mut typed_rows = vec![];
+        for row in rows {
+            if row.len() != columns.len() {
+                return Err(CIndexError::InvalidQueryStatement(format!(
+                    "Row has {} values but {} columns are given",
+                    row.len(),
+                    columns.len()
+                )));
+            }
+            let values = names
+                .iter()
+                .zip(&sources)
+                .map(|(name, source)| {
+                    let column_type = self.column_type(name);
+                    match source {
+                        Some(index) => Value::from_str(&row[*index], column_type).map_err(|_| {
+                            CIndexError::TypeDiscord(format!(
+                                "Value \"{}\" is not a {} of column \"{}\"",
+                                row[*index], column_type, name
+                            ))
+                        }),
+                        None if column_type == ValueType::Text => Ok(Value::Text(String::new())),
+                        None => Err(CIndexError::TypeDiscord(format!(
+                            "Column \"{}\" of {} type requires a value",
+                            name, column_type
+                        ))),
+                    }
+                })
+                .collect::<CIndexResult<Vec<_>>>()?;
+            typed_rows.push(values);
+        }
+        Ok(typed_rows)
+    }
 
-        // This is already a queried records, which means columns already persists for every
-        // records, therefore it is safe to get value and unwrap
-        match &query.order_type {
-            OrderType::Desc(col) | OrderType::Asec(col) => {
-                if self.header.contains(col) {
-                    queried.sort_by(|&a, &b| {
-                        let a = a.get_cell_value(col).unwrap();
-                        let b = b.get_cell_value(col).unwrap();
-                        if let OrderType::Desc(_) = &query.order_type {
-                            // Descending
-                            b.partial_cmp(a).unwrap()
-                        } else {
-                            // Aescending
-                            a.partial_cmp(b).unwrap()
+    /// Append typed rows and add them to indexes
+    fn push_rows(&mut self, typed_rows: Vec<Vec<Value>>) -> CIndexResult<()> {
+        let start = self.row_count();
+        let positions = self
+            .indexes
+            .keys()
+            .map(|column| self.column_position(column))
+            .collect::<CIndexResult<Vec<_>>>()?;
+        for (index, column) in self.indexes.values_mut().zip(positions) {
+            let index = Arc::make_mut(index);
+            for (offset, values) in typed_rows.iter().enumerate() {
+                index.insert(&values[column], start + offset);
+            }
+        }
+        let count = typed_rows.len();
+        let data = self.memory_mut()?;
+        for values in typed_rows {
+            data.push_row(values);
+        }
+        self.map_keys(&(start..start + count).collect::<Vec<_>>())
+    }
+
+    /// Update rows which qualify predicates of a query
+    ///
+    /// Every new value is validated before any row is updated. Indexes of updated columns are
+    /// rebuilt. This returns the number of updated rows.
+    pub(crate) fn update(
+        &mut self,
+        assignments: &[Assignment],
+        query: &Query,
+    ) -> CIndexResult<usize> {
+        self.check_memory()?;
+        let mut targets = vec![];
+        for assignment in assignments {
+            let column = self.column_position(&assignment.column)?;
+            if targets.iter().any(|(c, _)| *c == column) {
+                return Err(CIndexError::InvalidColumn(format!(
+                    "Column \"{}\" is assigned more than once",
+                    assignment.column
+                )));
+            }
+            let source = match assignment.expression.column() {
+                Some(source) => Some(self.column_position(source)?),
+                None => None,
+            };
+            targets.push((column, source));
+        }
+
+        let rows = self.query_rows(query)?;
+        let mut changes = vec![];
+        for (assignment, (column, source)) in assignments.iter().zip(&targets) {
+            let column_type = self.column_type(&assignment.column);
+            let values = rows
+                .iter()
+                .map(|(position, row)| {
+                    let value = assignment
+                        .expression
+                        .evaluate(source.map(|source| row.get(source)));
+                    Value::from_str(&value, column_type)
+                        .map(|value| (*position, value))
+                        .map_err(|_| {
+                            CIndexError::TypeDiscord(format!(
+                                "Value \"{}\" is not a {} of column \"{}\"",
+                                value, column_type, assignment.column
+                            ))
+                        })
+                })
+                .collect::<CIndexResult<Vec<_>>>()?;
+            changes.push((*column, values));
+        }
+        if !self.constraints.is_empty() || self.primary_key.is_some() {
+            let width = self.header.len();
+            let mut updated = rows
+                .iter()
+                .map(|(position, row)| {
+                    (
+                        *position,
+                        (0..width).map(|c| row.get(c).clone()).collect::<Vec<_>>(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            for (column, values) in &changes {
+                for ((_, row), (_, value)) in updated.iter_mut().zip(values) {
+                    row[*column] = value.clone();
+                }
+            }
+            self.check_rows(
+                &updated
+                    .iter()
+                    .map(|(position, row)| (*position, row.as_slice()))
+                    .collect::<Vec<_>>(),
+            )?;
+        }
+        let positions = rows
+            .iter()
+            .map(|(position, _)| *position)
+            .collect::<Vec<_>>();
+        let count = positions.len();
+        drop(rows);
+
+        self.unmap_keys(&positions)?;
+        let data = self.memory_mut()?;
+        for (column, values) in changes {
+            data.set_values(column, values);
+        }
+        self.map_keys(&positions)?;
+        for assignment in assignments {
+            if let Some(index_type) = self.indexes.get(&assignment.column).map(|i| i.index_type()) {
+                self.create_index(&assignment.column, index_type)?;
+            }
+        }
+        if count > 0 {
+            self.edited();
+        }
+        Ok(count)
+    }
+
+    /// Delete rows which qualify predicates of a query
+    ///
+    /// Every index of the table is rebuilt because positions of rows change. This returns the
+    /// number of deleted rows.
+    pub(crate) fn delete(&mut self, query: &Query) -> CIndexResult<usize> {
+        self.check_memory()?;
+        let mut removed = vec![false; self.row_count()];
+        for (position, _) in self.query_rows(query)? {
+            removed[position] = true;
+        }
+        let count = removed.iter().filter(|removed| **removed).count();
+        if count == 0 {
+            return Ok(0);
+        }
+
+        self.memory_mut()?.remove_rows(&removed);
+        self.remap_keys(&removed);
+        let indexes = self
+            .indexes
+            .iter()
+            .map(|(column, index)| (column.to_owned(), index.index_type()))
+            .collect::<Vec<_>>();
+        for (column, index_type) in indexes {
+            self.create_index(&column, index_type)?;
+        }
+        self.edited();
+        Ok(count)
+    }
+
+    /// Add a text column whose every row has a default value
+    pub(crate) fn add_column(&mut self, column: &str, default: &str) -> CIndexResult<()> {
+        if self.header.contains(column) {
+            return Err(CIndexError::InvalidColumn(format!(
+                "Column \"{}\" already exists",
+                column
+            )));
+        }
+        self.memory_mut()?.add_column(
+            column.to_owned(),
+            ValueType::Text,
+            Value::Text(default.to_owned()),
+        );
+        self.header.insert(column.to_owned());
+        self.edited();
+        Ok(())
+    }
+
+    /// Drop a column with its index
+    pub(crate) fn drop_column(&mut self, column: &str) -> CIndexResult<()> {
+        let position = self.column_position(column)?;
+        if self
+            .primary_key
+            .as_ref()
+            .map(|key| key.columns.iter().any(|c| c == column))
+            .unwrap_or(false)
+        {
+            return Err(CIndexError::InvalidColumn(format!(
+                "Cannot drop \"{}\" which is a primary key column",
+                column
+            )));
+        }
+        if let Some(constraint) = self
+            .constraints
+            .iter()
+            .find(|constraint| constraint.columns().contains(&column))
+        {
+            return Err(CIndexError::InvalidColumn(format!(
+                "Cannot drop \"{}\" which is used by {}",
+                column, constraint
+            )));
+        }
+        if self.header.len() == 1 {
+            return Err(CIndexError::InvalidColumn(format!(
+                "Cannot drop \"{}\" which is the only column of table",
+                column
+            )));
+        }
+        self.memory_mut()?.remove_column(position);
+        self.header.remove(column);
+        self.indexes.remove(column);
+        self.edited();
+        Ok(())
+    }
+
+    /// Rename a column so that later queries use the new name
+    pub(crate) fn rename_column(&mut self, from: &str, to: &str) -> CIndexResult<()> {
+        let position = self.column_position(from)?;
+        if self.header.contains(to) {
+            return Err(CIndexError::InvalidColumn(format!(
+                "Column \"{}\" already exists",
+                to
+            )));
+        }
+        self.memory_mut()?.columns[position].name = to.to_owned();
+        self.header.remove(from);
+        self.header.insert(to.to_owned());
+        if let Some(index) = self.indexes.remove(from) {
+            self.indexes.insert(to.to_owned(), index);
+        }
+        for constraint in self.constraints.iter_mut() {
+            constraint.rename_column(from, to);
+        }
+        for key in self.keys_mut() {
+            for column in key.columns.iter_mut().filter(|c| *c == from) {
+                *column = to.to_owned();
+            }
+        }
+        self.edited();
+        Ok(())
+    }
+
+    /// Refresh statistics after rows or columns have changed
+    ///
+    /// Edited table is detached from its source file, so that a cache keeps the edits instead
+    /// of reading the file again.
+    fn edited(&mut self) {
+        self.collect_stats();
+        self.source = None;
+    }
+
+    /// Find rows which don't satisfy constraints
+    pub(crate) fn violations(&self, constraints: &[Constraint]) -> CIndexResult<Vec<Violation>> {
+        let mut violations = vec![];
+        let mut violate = |row: usize, constraint: &Constraint| {
+            violations.push(Violation {
+                row,
+                constraint: constraint.clone(),
+            })
+        };
+        for constraint in constraints {
+            match constraint {
+                Constraint::NotNull(column) => {
+                    let column = self.column_position(column)?;
+                    for position in 0..self.row_count() {
+                        if is_null(self.record(position).get(column)) {
+                            violate(position, constraint);
                         }
+                    }
+                }
+                Constraint::Unique(columns) => {
+                    let columns = columns
+                        .iter()
+                        .map(|c| self.column_position(c))
+                        .collect::<CIndexResult<Vec<_>>>()?;
+                    let mut keys = HashSet::new();
+                    for position in 0..self.row_count() {
+                        let record = self.record(position);
+                        let key = columns
+                            .iter()
+                            .map(|column| record.get(*column).to_string())
+                            .collect::<Vec<_>>();
+                        if !is_empty_key(&key) && !keys.insert(key) {
+                            violate(position, constraint);
+                        }
+                    }
+                }
+                Constraint::Check(predicates) => {
+                    let predicates = predicates
+                        .iter()
+                        .map(|pre| self.resolve(pre))
+                        .collect::<CIndexResult<Vec<_>>>()?;
+                    for position in 0..self.row_count() {
+                        let record = self.record(position);
+                        let satisfied = predicates.iter().all(|pre| {
+                            let value = record.get(pre.column);
+                            is_null(value) || pre.matches(value)
+                        });
+                        if !satisfied {
+                            violate(position, constraint);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(violations)
+    }
+
+    /// Check rows which are about to be written against constraints
+    ///
+    /// Every row has values in order of columns and its position, which is past the last row
+    /// for a new row. Only given rows are checked and their unique values are compared with
+    /// keys of other rows, so that a check doesn't scan the whole table.
+    fn check_rows(&self, rows: &[(usize, &[Value])]) -> CIndexResult<()> {
+        let mut violations = vec![];
+        if let Some(key) = &self.primary_key {
+            let columns = self.key_columns(key)?;
+            for (position, values) in rows {
+                if let Some(index) = columns.iter().position(|c| is_null(&values[*c])) {
+                    violations.push(Violation {
+                        row: *position,
+                        constraint: Constraint::NotNull(key.columns[index].to_owned()),
                     });
-                } else {
-                    return Err(CIndexError::InvalidQueryStatement(format!(
-                        "Column \"{}\" doesn't exist",
-                        col
-                    )));
                 }
             }
-            _ => (),
+            violations.extend(
+                self.key_conflicts(key, rows)?
+                    .into_iter()
+                    .map(|row| Violation {
+                        row,
+                        constraint: Constraint::Unique(key.columns.to_vec()),
+                    }),
+            );
         }
+        let mut unique_keys = self.unique_keys.iter();
+        for constraint in &self.constraints {
+            let violated = match constraint {
+                Constraint::NotNull(column) => {
+                    let column = self.column_position(column)?;
+                    rows.iter()
+                        .filter(|(_, values)| is_null(&values[column]))
+                        .map(|(position, _)| *position)
+                        .collect()
+                }
+                Constraint::Unique(_) => match unique_keys.next() {
+                    Some(key) => self.key_conflicts(key, rows)?,
+                    None => vec![],
+                },
+                Constraint::Check(predicates) => {
+                    let predicates = predicates
+                        .iter()
+                        .map(|pre| self.resolve(pre))
+                        .collect::<CIndexResult<Vec<_>>>()?;
+                    rows.iter()
+                        .filter(|(_, values)| {
+                            !predicates.iter().all(|pre| {
+                                let value = &values[pre.column];
+                                is_null(value) || pre.matches(value)
+                            })
+                        })
+                        .map(|(position, _)| *position)
+                        .collect()
+                }
+            };
+            violations.extend(violated.into_iter().map(|row| Violation {
+                row,
+                constraint: constraint.clone(),
+            }));
+        }
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(CIndexError::ConstraintViolation(violations))
+        }
+    }
 
-        // If offset or limit has been provided
-        // Slice it
-        if query.range.0 != 0 || query.range.1 != 0 {
-            let offset = (query.range.0).min(queried.len());
-            let limit = query.range.1;
+    /// Find rows whose key is given twice or is taken by a row which is not given
+    ///
+    /// Empty key never conflicts, which is rejected by not null rules instead.
+    fn key_conflicts(
+        &self,
+        key: &UniqueKey,
+        rows: &[(usize, &[Value])],
+    ) -> CIndexResult<Vec<usize>> {
+        let columns = self.key_columns(key)?;
+        let given = rows
+            .iter()
+            .map(|(position, _)| *position)
+            .collect::<HashSet<_>>();
+        let mut keys = HashSet::with_capacity(rows.len());
+        let mut conflicts = vec![];
+        for (position, values) in rows {
+            let values = columns
+                .iter()
+                .map(|column| values[*column].to_string())
+                .collect::<Vec<_>>();
+            if is_empty_key(&values) {
+                continue;
+            }
+            let taken = key
+                .map
+                .get(&values)
+                .map(|row| !given.contains(row))
+                .unwrap_or(false);
+            if taken || !keys.insert(values) {
+                conflicts.push(*position);
+            }
+        }
+        Ok(conflicts)
+    }
+
+    /// Get positions of key columns
+    fn key_columns(&self, key: &UniqueKey) -> CIndexResult<Vec<usize>> {
+        key.columns
+            .iter()
+            .map(|c| self.column_position(c))
+            .collect()
+    }
+
+    /// Get primary key and keys of unique constraints
+    fn keys(&self) -> impl Iterator<Item = &UniqueKey> + '_ {
+        self.primary_key.iter().chain(self.unique_keys.iter())
+    }
+
+    /// Get primary key and keys of unique constraints to update them along with rows
+    fn keys_mut(&mut self) -> impl Iterator<Item = &mut UniqueKey> + '_ {
+        self.primary_key
+            .iter_mut()
+            .chain(self.unique_keys.iter_mut())
+    }
+
+    /// Get values of rows for every key in order of rows
+    fn row_keys(&self, rows: &[usize]) -> CIndexResult<Vec<Vec<Vec<String>>>> {
+        self.keys()
+            .map(|key| {
+                let columns = self.key_columns(key)?;
+                Ok(rows
+                    .iter()
+                    .map(|position| {
+                        let record = self.record(*position);
+                        columns
+                            .iter()
+                            .map(|column| record.get(*column).to_string())
+                            .collect()
+                    })
+                    .collect())
+            })
+            .collect()
+    }
+
+    /// Add rows to keys after rows are written
+    fn map_keys(&mut self, rows: &[usize]) -> CIndexResult<()> {
+        let keys = self.row_keys(rows)?;
+        for (key, values) in self.keys_mut().zip(keys) {
+            Arc::make_mut(&mut key.map).extend(
+                values
+                    .into_iter()
+                    .zip(rows.iter().copied())
+                    .filter(|(values, _)| !is_empty_key(values)),
+            );
+        }
+        Ok(())
+    }
 
-            let query_limit = if limit == 0 {
-                queried.len()
+    /// Remove rows from keys before values of rows change
+    fn unmap_keys(&mut self, rows: &[usize]) -> CIndexResult<()> {
+        let keys = self.row_keys(rows)?;
+        for (key, values) in self.keys_mut().zip(keys) {
+            for (values, position) in values.into_iter().zip(rows) {
+                if key.map.get(&values) == Some(position) {
+                    Arc::make_mut(&mut key.map).remove(&values);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Shift row positions of keys after rows are removed
+    fn remap_keys(&mut self, removed: &[bool]) {
+        let mut kept = 0;
+        let positions = removed
+            .iter()
+            .map(|removed| {
+                let position = kept;
+                if !removed {
+                    kept += 1;
+                }
+                position
+            })
+            .collect::<Vec<_>>();
+        for key in self.keys_mut() {
+            Arc::make_mut(&mut key.map).retain(|_, position| {
+                if removed[*position] {
+                    false
+                } else {
+                    *position = positions[*position];
+                    true
+                }
+            });
+        }
+    }
+
+    /// Map values of key columns to row positions
+    ///
+    /// Every row whose key is duplicate, or empty if keys must not be null, is reported as a
+    /// violation. Otherwise row whose every key column is empty is not mapped.
+    pub(crate) fn key_map(
+        &self,
+        columns: &[String],
+        not_null: bool,
+    ) -> CIndexResult<HashMap<Vec<String>, usize>> {
+        let positions = columns
+            .iter()
+            .map(|c| self.column_position(c))
+            .collect::<CIndexResult<Vec<_>>>()?;
+        let mut map = HashMap::with_capacity(self.row_count());
+        let mut violations = vec![];
+        for position in 0..self.row_count() {
+            let record = self.record(position);
+            let key = positions
+                .iter()
+                .map(|column| record.get(*column).to_string())
+                .collect::<Vec<_>>();
+            let empty = key.iter().position(|v| not_null && v.is_empty());
+            let constraint = if let Some(index) = empty {
+                Constraint::NotNull(columns[index].to_owned())
+            } else if is_empty_key(&key) {
+                continue;
+            } else if let Entry::Vacant(entry) = map.entry(key) {
+                entry.insert(position);
+                continue;
             } else {
-                (offset + limit).min(queried.len())
+                Constraint::Unique(columns.to_vec())
             };
-            queried = queried[offset..query_limit].to_vec();
+            violations.push(Violation {
+                row: position,
+                constraint,
+            });
+        }
+        if violations.is_empty() {
+            Ok(map)
+        } else {
+            Err(CIndexError::ConstraintViolation(violations))
+        }
+    }
+
+    /// Rebuild a map of primary key from every row
+    ///
+    /// This is only needed when a table is loaded, because the map is updated along with rows.
+    pub(crate) fn build_key(&mut self) -> CIndexResult<()> {
+        if let Some(key) = &self.primary_key {
+            let map = self.key_map(&key.columns, true)?;
+            if let Some(key) = self.primary_key.as_mut() {
+                key.map = Arc::new(map);
+            }
+        }
+        Ok(())
+    }
+
+    /// Find a row position of primary key values
+    pub(crate) fn key_position(&self, values: &[&str]) -> CIndexResult<Option<usize>> {
+        let key = self.primary_key.as_ref().ok_or_else(|| {
+            CIndexError::InvalidQueryStatement("Table doesn't have a primary key".to_owned())
+        })?;
+        if values.len() != key.columns.len() {
+            return Err(CIndexError::InvalidQueryStatement(format!(
+                "Primary key has {} columns but {} values are given",
+                key.columns.len(),
+                values.len()
+            )));
+        }
+        let values = key
+            .columns
+            .iter()
+            .zip(values)
+            .map(|(column, value)| {
+                let column_type = self.column_type(column);
+                Value::from_str(value, column_type)
+                    .map(|value| value.to_string())
+                    .map_err(|_| {
+                        CIndexError::TypeDiscord(format!(
+                            "Value \"{}\" is not a {} of column \"{}\"",
+                            value, column_type, column
+                        ))
+                    })
+            })
+            .collect::<CIndexResult<Vec<_>>>()?;
+        Ok(key.map.get(&values).copied())
+    }
+
+    /// Replace constraints of the table after every row is checked against them
+    ///
+    /// Keys of unique constraints are built here and then kept along with rows.
+    pub(crate) fn set_constraints(&mut self, constraints: Vec<Constraint>) -> CIndexResult<()> {
+        let violations = self.violations(&constraints)?;
+        if !violations.is_empty() {
+            return Err(CIndexError::ConstraintViolation(violations));
+        }
+        let mut unique_keys = vec![];
+        for constraint in &constraints {
+            if let Constraint::Unique(columns) = constraint {
+                let mut key = UniqueKey::new(columns.to_vec());
+                key.map = Arc::new(self.key_map(columns, false)?);
+                unique_keys.push(key);
+            }
+        }
+        self.constraints = constraints;
+        self.unique_keys = unique_keys;
+        Ok(())
+    }
+
+    /// Find a predicate which can be served by an index
+    ///
+    /// Equality predicates are preferred over range predicates.
+    /// This returns the position of the predicate and the index.
+    pub(crate) fn find_index(&self, predicates: &[Predicate]) -> Option<(usize, &ColumnIndex)> {
+        let served = |pre: &Predicate| {
+            self.indexes
+                .get(&pre.column)
+                .map(|index| index.as_ref())
+                .filter(|index| index.serves(pre.operation))
+        };
+        let equality = predicates.iter().enumerate().find_map(|(position, pre)| {
+            match pre.operation {
+                Operator::Equal | Operator::In => served(pre),
+                _ => None,
+            }
+            .map(|index| (position, index))
+        });
+        equality.or_else(|| {
+            predicates
+                .iter()
+                .enumerate()
+                .find_map(|(position, pre)| served(pre).map(|index| (position, index)))
+        })
+    }
+
+    /// Get an ordered index which can replace sorting of a query
+    pub(crate) fn find_order_index(&self, query: &Query) -> Option<&OrderedIndex> {
+        match &query.order_type {
+            OrderType::Asec(col) | OrderType::Desc(col) => {
+                match self.indexes.get(col).map(|index| index.as_ref()) {
+                    Some(ColumnIndex::Ordered(index)) => Some(index),
+                    _ => None,
+                }
+            }
+            OrderType::None => None,
+        }
+    }
+
+    /// Get rows positions which qualify a predicate with an index
+    pub(crate) fn lookup(
+        &self,
+        index: &ColumnIndex,
+        predicate: &Predicate,
+    ) -> CIndexResult<Vec<usize>> {
+        Ok(index.lookup(predicate.operation, &self.typed_arguments(predicate)?))
+    }
+
+    /// Convert predicate's arguments into column's type
+    ///
+    /// Like's arguments are not converted because it always matches text.
+    fn typed_arguments(&self, predicate: &Predicate) -> CIndexResult<Vec<Value>> {
+        let column_type = self.column_type(&predicate.column);
+        let column_type = if predicate.operation == Operator::Like {
+            ValueType::Text
+        } else {
+            column_type
+        };
+
+        predicate
+            .arguments
+            .iter()
+            .map(|arg| {
+                Value::from_str(arg, column_type).map_err(|_| {
+                    CIndexError::InvalidQueryStatement(format!(
+                        "Argument \"{}\" cannot be compared with {} column \"{}\"",
+                        arg, column_type, predicate.column
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    /// Collect statistics of every column
+    ///
+    /// Mapped table doesn't have statistics because it would read every row.
+    pub(crate) fn collect_stats(&mut self) {
+        self.stats = match &self.storage {
+            #[cfg(feature = "rayon")]
+            Storage::Memory(data) => data.columns.par_iter().map(|c| c.stats()).collect(),
+            #[cfg(not(feature = "rayon"))]
+            Storage::Memory(data) => data.columns.iter().map(|c| c.stats()).collect(),
+            #[cfg(feature = "mmap")]
+            Storage::Mapped(_) => vec![],
+        };
+    }
+
+    /// Estimate a ratio of rows which qualify a resolved predicate
+    pub(crate) fn selectivity(&self, predicate: &ResolvedPredicate) -> f64 {
+        let operator = predicate.predicate.operation;
+        match self.stats.get(predicate.column) {
+            Some(stats) => stats.selectivity(operator, &predicate.arguments),
+            None => default_selectivity(operator, predicate.arguments.len()),
         }
+    }
+
+    /// Check if no row can qualify a resolved predicate with column's min and max
+    pub(crate) fn excludes(&self, predicate: &ResolvedPredicate) -> bool {
+        self.stats
+            .get(predicate.column)
+            .map(|stats| stats.excludes(predicate.predicate.operation, &predicate.arguments))
+            .unwrap_or(false)
+    }
+
+    /// Order predicates so that selective ones are evaluated first
+    ///
+    /// Like is always evaluated last because matching a regex is the most expensive. Predicates
+    /// with a same selectivity keep their declaration order.
+    pub(crate) fn order_predicates(&self, predicates: &mut [ResolvedPredicate]) {
+        predicates.sort_by(|a, b| {
+            let is_like = |pre: &ResolvedPredicate| pre.predicate.operation == Operator::Like;
+            is_like(a).cmp(&is_like(b)).then(
+                self.selectivity(a)
+                    .partial_cmp(&self.selectivity(b))
+                    .unwrap_or(Ordering::Equal),
+            )
+        });
+    }
+
+    /// Check if query's columns exist in the table
+    pub(crate) fn validate(&self, query: &Query) -> CIndexResult<()> {
+        for col in &query.column_names {
+            if col != "*" && !self.header.contains(col) && !query.flags.contains(QueryFlagType::Sup)
+            {
+                return Err(CIndexError::InvalidQueryStatement(format!(
+                    "Column \"{}\" doesn't exist",
+                    col
+                )));
+            }
+        }
+
+        for item in query.predicates.iter().flatten() {
+            item.check_arguments()?;
+            if !self.header.contains(&item.column) {
+                return Err(CIndexError::InvalidColumn(format!(
+                    "Failed to get column \"{}\" from header",
+                    item.column
+                )));
+            }
+        }
+
+        if let OrderType::Desc(col) | OrderType::Asec(col) = &query.order_type {
+            if !self.header.contains(col) {
+                return Err(CIndexError::InvalidQueryStatement(format!(
+                    "Column \"{}\" doesn't exist",
+                    col
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn query(&self, query: &Query) -> CIndexResult<Vec<Record<'_>>> {
+        Ok(self
+            .query_rows(query)?
+            .into_iter()
+            .map(|(_, row)| row)
+            .collect())
+    }
+
+    /// Query rows with columns which were resolved for a table of the same columns
+    pub(crate) fn query_resolved(
+        &self,
+        query: &Query,
+        columns: &ResolvedColumns,
+    ) -> CIndexResult<Vec<Record<'_>>> {
+        Ok(self
+            .query_positions(query, &columns.predicates, columns.order)?
+            .into_iter()
+            .map(|(_, row)| row)
+            .collect())
+    }
+
+    /// Get positions of predicates' columns and an order column of a query
+    pub(crate) fn query_columns(&self, query: &Query) -> CIndexResult<(Vec<usize>, Option<usize>)> {
+        let predicates = query
+            .predicates
+            .iter()
+            .flatten()
+            .map(|pre| self.column_position(&pre.column))
+            .collect::<CIndexResult<Vec<_>>>()?;
+        let order = match &query.order_type {
+            OrderType::Desc(col) | OrderType::Asec(col) => Some(self.column_position(col)?),
+            OrderType::None => None,
+        };
+        Ok((predicates, order))
+    }
+
+    /// Query rows with their positions
+    fn query_rows(&self, query: &Query) -> CIndexResult<Vec<(usize, Record<'_>)>> {
+        self.validate(query)?;
+        let (columns, order) = self.query_columns(query)?;
+        self.query_positions(query, &columns, order)
+    }
+
+    /// Query rows with resolved positions of predicates' columns and an order column
+    fn query_positions(
+        &self,
+        query: &Query,
+        columns: &[usize],
+        order: Option<usize>,
+    ) -> CIndexResult<Vec<(usize, Record<'_>)>> {
+        if query.has_placeholders() {
+            return Err(CIndexError::InvalidQueryStatement(
+                "Query has placeholders without bound values".to_owned(),
+            ));
+        }
+
+        let boilerplate = vec![];
+        let predicates = query.predicates.as_ref().unwrap_or(&boilerplate);
+        let resolved = predicates
+            .iter()
+            .zip(columns)
+            .map(|(pre, column)| {
+                Ok(ResolvedPredicate {
+                    column: *column,
+                    predicate: pre,
+                    arguments: self.typed_arguments(pre)?,
+                })
+            })
+            .collect::<CIndexResult<Vec<_>>>()?;
+
+        // No row qualifies a predicate which is out of column's range
+        if resolved.iter().any(|pre| self.excludes(pre)) {
+            return Ok(vec![]);
+        }
+
+        let indexed = self.find_index(predicates);
+        let order_index = if indexed.is_none() {
+            self.find_order_index(query)
+        } else {
+            None
+        };
+
+        // Candidate rows in order. None means every row.
+        let candidates = if let Some((position, index)) = indexed {
+            Some(self.lookup(index, &predicates[position])?)
+        } else {
+            order_index.map(|index| index.positions(matches!(query.order_type, OrderType::Desc(_))))
+        };
+
+        // Skip the indexed predicate
+        let mut predicates = resolved
+            .into_iter()
+            .enumerate()
+            .filter(|(position, _)| !matches!(indexed, Some((p, _)) if p == *position))
+            .map(|(_, pre)| pre)
+            .collect::<Vec<_>>();
+        self.order_predicates(&mut predicates);
+
+        // Rows from ordered index are already sorted
+        let descending = matches!(query.order_type, OrderType::Desc(_));
+        let sort = order
+            .filter(|_| order_index.is_none())
+            .map(|column| (column, descending));
+        let (offset, limit) = query.range;
+        let wanted = if limit == 0 {
+            None
+        } else {
+            Some(offset.saturating_add(limit))
+        };
+
+        let mut queried: Vec<(usize, Record)> = match (sort, wanted) {
+            // Scanning stops as soon as enough rows are found
+            (None, Some(wanted)) => self
+                .matching(candidates.as_deref(), &predicates)
+                .take(wanted)
+                .collect(),
+            // Only rows within limit are kept while scanning
+            (Some((column, descending)), Some(wanted)) => top_rows(
+                self.matching(candidates.as_deref(), &predicates),
+                column,
+                descending,
+                wanted,
+            ),
+            _ => {
+                let mut queried: Vec<(usize, Record)> = if let Some(rows) = candidates {
+                    #[cfg(feature = "rayon")]
+                    let iter = rows.par_iter();
+                    #[cfg(not(feature = "rayon"))]
+                    let iter = rows.iter();
+
+                    iter.map(|&position| (position, self.record(position)))
+                        .filter(|(_, row)| self.filter(row, &predicates))
+                        .collect()
+                } else {
+                    #[cfg(feature = "rayon")]
+                    let iter = (0..self.row_count()).into_par_iter();
+                    #[cfg(not(feature = "rayon"))]
+                    let iter = 0..self.row_count();
+
+                    iter.map(|position| (position, self.record(position)))
+                        .filter(|(_, row)| self.filter(row, &predicates))
+                        .collect()
+                };
+
+                // Values of a same column are always comparable
+                if let Some((column, descending)) = sort {
+                    let compare = |(_, a): &(usize, Record), (_, b): &(usize, Record)| {
+                        let ordering = a.get(column).partial_cmp(b.get(column)).unwrap();
+                        if descending {
+                            ordering.reverse()
+                        } else {
+                            ordering
+                        }
+                    };
+                    #[cfg(feature = "rayon")]
+                    queried.par_sort_by(compare);
+                    #[cfg(not(feature = "rayon"))]
+                    queried.sort_by(compare);
+                }
+                queried
+            }
+        };
+
+        // If offset has been provided
+        // Slice it
+        queried.drain(..offset.min(queried.len()));
 
         Ok(queried)
     }
 
+    /// Iterate rows which qualify predicates with their positions in order
+    ///
+    /// Candidates are rows to check, None means every row.
+    fn matching<'t: 'a, 'a>(
+        &'t self,
+        candidates: Option<&'a [usize]>,
+        predicates: &'a [ResolvedPredicate],
+    ) -> impl Iterator<Item = (usize, Record<'t>)> + 'a {
+        let positions: Box<dyn Iterator<Item = usize> + 'a> = match candidates {
+            Some(rows) => Box::new(rows.iter().copied()),
+            None => Box::new(0..self.row_count()),
+        };
+        positions
+            .map(move |position| (position, self.record(position)))
+            .filter(move |(_, row)| self.filter(row, predicates))
+    }
+
     /// Iterator method
     // This should not return result because it is usesd in filter_adopter
-    fn filter(&self, row: &Row, predicates: &Vec<Predicate>) -> bool {
-        for pre in predicates {
-            let column = pre.column.as_str();
-            if !operate_value(
-                &row.get_cell_value(column).unwrap().to_string(),
-                &pre.arguments,
-                pre,
-            ) {
-                return false;
-            }
+    fn filter(&self, row: &Record, predicates: &[ResolvedPredicate]) -> bool {
+        predicates
+            .iter()
+            .all(|pre| pre.matches(row.get(pre.column)))
+    }
+}
+
+/// Row whose values can be ranked by a column
+pub(crate) trait RankValue {
+    fn rank_value(&self, column: usize) -> &Value;
+}
+
+impl<'a> RankValue for Record<'a> {
+    fn rank_value(&self, column: usize) -> &Value {
+        self.get(column)
+    }
+}
+
+impl RankValue for Vec<Value> {
+    fn rank_value(&self, column: usize) -> &Value {
+        &self[column]
+    }
+}
+
+/// Row which is ordered by a column and then by its position
+struct RankedRow<R> {
+    position: usize,
+    row: R,
+    column: usize,
+    descending: bool,
+}
+
+impl<R: RankValue> Ord for RankedRow<R> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Values of a same column are always comparable
+        let ordering = self
+            .row
+            .rank_value(self.column)
+            .partial_cmp(other.row.rank_value(self.column))
+            .unwrap_or(Ordering::Equal);
+        let ordering = if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        ordering.then(self.position.cmp(&other.position))
+    }
+}
+
+impl<R: RankValue> PartialOrd for RankedRow<R> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<R: RankValue> PartialEq for RankedRow<R> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<R: RankValue> Eq for RankedRow<R> {}
+
+/// First rows in order of a column which are collected without sorting every row
+///
+/// Heap keeps at most given count of rows and drops the last one when it overflows. Rows of a
+/// same value keep their order as a stable sort does.
+pub(crate) struct TopRows<R> {
+    heap: BinaryHeap<RankedRow<R>>,
+    column: usize,
+    descending: bool,
+    count: usize,
+}
+
+impl<R: RankValue> TopRows<R> {
+    pub fn new(column: usize, descending: bool, count: usize) -> Self {
+        Self {
+            // Count can be far more than rows with a large offset
+            heap: BinaryHeap::new(),
+            column,
+            descending,
+            count,
+        }
+    }
+
+    /// Add a row of a position, which drops the last row if there are too many
+    pub fn push(&mut self, position: usize, row: R) {
+        self.heap.push(RankedRow {
+            position,
+            row,
+            column: self.column,
+            descending: self.descending,
+        });
+        if self.heap.len() > self.count {
+            self.heap.pop();
         }
+    }
+
+    /// Get kept rows in order
+    pub fn into_sorted(self) -> Vec<(usize, R)> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|r| (r.position, r.row))
+            .collect()
+    }
+}
 
-        true
+/// Get first rows in order of a column without sorting every row
+fn top_rows<'a>(
+    rows: impl Iterator<Item = (usize, Record<'a>)>,
+    column: usize,
+    descending: bool,
+    count: usize,
+) -> Vec<(usize, Record<'a>)> {
+    let mut top = TopRows::new(column, descending, count);
+    for (position, row) in rows {
+        top.push(position, row);
     }
+    top.into_sorted()
 }
 
-fn operate_value(comparator: &str, values: &[String], pre: &Predicate) -> bool {
-    let var = comparator;
-    let arg = values[0].as_str();
+/// Compare a value with typed arguments
+fn operate_value(var: &Value, values: &[Value], pre: &Predicate) -> bool {
+    let arg = &values[0];
     let operation = &pre.operation;
     match operation {
         // It is safe to unwrap, because Like panics when matcher doesn't compiled
-        Operator::Like => pre.matcher.as_ref().unwrap().is_match(var),
+        Operator::Like => match var {
+            Value::Text(text) => pre.matcher.as_ref().unwrap().is_match(text),
+            Value::Number(_) => pre.matcher.as_ref().unwrap().is_match(&var.to_string()),
+        },
         Operator::Bigger => var > arg,
         Operator::BiggerOrEqual => var >= arg,
         Operator::Smaller => var < arg,
         Operator::SmallerOrEqual => var <= arg,
         Operator::Equal => var == arg,
         Operator::NotEqual => var != arg,
-        Operator::In => values.contains(&var.to_owned()),
+        Operator::In => values.contains(var),
         Operator::Between => var >= &values[0] && var <= &values[1],
     }
 }
 
+/// Check if a value is null, which is an empty text
+fn is_null(value: &Value) -> bool {
+    matches!(value, Value::Text(text) if text.is_empty())
+}
+
+/// Check if every value of a key is empty, which is never a duplicate of another key
+fn is_empty_key(key: &[String]) -> bool {
+    key.iter().all(|value| value.is_empty())
+}
+
 impl Display for Table {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.data)
+        let columns = self.column_names();
+        let positions = (0..columns.len()).map(Some).collect::<Vec<_>>();
+        writeln!(f, "{}", columns.join(","))?;
+        for position in 0..self.row_count() {
+            writeln!(f, "{}", self.record(position).project(&positions).join(","))?;
+        }
+        Ok(())
     }
 }
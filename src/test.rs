@@ -1,10 +1,15 @@
 #[cfg(test)]
 mod tests {
+    use crate::columnar::Cells;
     use crate::indexer::{Indexer, OutOption};
-    use crate::ReaderOption;
-    use crate::{CIndexResult, Operator, Predicate, Query};
+    use crate::table::Storage;
+    use crate::IndexType;
+    use crate::{AccessPath, ReaderOption};
+    use crate::{CIndexError, CIndexResult, Operator, Params, Predicate, Query};
+    use crate::{Constraint, QuoteStyle, WriterOption};
     use std::fs::File;
     use std::io::BufReader;
+    use std::str::FromStr;
 
     #[test]
     fn test_function() -> CIndexResult<()> {
@@ -40,11 +45,9 @@ mod tests {
         reader_option.ignore_empty_row = true;
         reader_option.trim = true;
         reader_option.consume_dquote = true;
-        indexer.add_table_with_option(
-            "t4",
-            BufReader::new(File::open("/home/simon/misc/csv_samples/biostats.csv")?),
-            reader_option,
-        )?;
+        if let Ok(file) = File::open("/home/simon/misc/csv_samples/biostats.csv") {
+            indexer.add_table_with_option("t4", BufReader::new(file), reader_option)?;
+        }
         indexer.add_table(
             "t1",
             "id,first name,last name,address
@@ -71,4 +74,1379 @@ mod tests {
 
         Ok(())
     }
+
+    fn sample_indexer() -> CIndexResult<Indexer> {
+        let mut indexer = Indexer::new();
+        indexer.always_use_unix_newline(true);
+        indexer.add_table(
+            "t1",
+            "id,first name,last name,address
+1,John,doe,AA 1234
+2,Janet,doner,BB 4566
+3,Hevay,jojo,CC 8790"
+                .as_bytes(),
+        )?;
+        Ok(indexer)
+    }
+
+    #[test]
+    fn between_and_in_syntax() -> CIndexResult<()> {
+        let indexer = sample_indexer()?;
+
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT id FROM t1 WHERE id BETWEEN 2 AND 3 AND address != 'CC 8790'",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("2\n", acc);
+
+        // Separators are keywords of any case
+        let mut acc = String::new();
+        indexer.index_raw(
+            "select id from t1 where id between 2 and 3 and address != 'CC 8790'",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("2\n", acc);
+
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT id FROM t1 WHERE address IN ('AA 1234', 'CC 8790')",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("1\n3\n", acc);
+
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT id FROM t1 WHERE id IN (2,3) AND id BETWEEN 1 2",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("2\n", acc);
+
+        for malformed in [
+            "SELECT id FROM t1 WHERE id BETWEEN 5",
+            "SELECT id FROM t1 WHERE id BETWEEN 1 AND 2 3",
+            "SELECT id FROM t1 WHERE id IN ()",
+            "SELECT id FROM t1 WHERE id = 1 2",
+        ] {
+            assert!(matches!(
+                Query::from_str(malformed),
+                Err(CIndexError::InvalidQueryStatement(_))
+            ));
+        }
+
+        let query = Query::build()
+            .table("t1")
+            .columns(vec!["id"])
+            .predicate(Predicate::new("id", Operator::Between).args(vec!["1"]));
+        assert!(matches!(
+            indexer.index(query, OutOption::Value(&mut String::new())),
+            Err(CIndexError::InvalidQueryStatement(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn bind_placeholders() -> CIndexResult<()> {
+        let indexer = sample_indexer()?;
+
+        let mut acc = String::new();
+        indexer.index_with_params(
+            "SELECT id FROM t1 WHERE id IN (?, ?) AND 'last name' = :last",
+            &Params::new().arg(1).arg(2).named("last", "doner"),
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("2\n", acc);
+
+        // Bound value is never parsed as a query statement
+        let mut acc = String::new();
+        indexer.index_with_params(
+            "SELECT id FROM t1 WHERE 'first name' = ?",
+            &Params::new().arg("x' OR id = '1"),
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("", acc);
+
+        let mut acc = String::new();
+        indexer.index_with_params(
+            "SELECT id FROM t1 WHERE address LIKE :pattern AND id != '?'",
+            &Params::new().named("pattern", "^[AB]"),
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("1\n2\n", acc);
+
+        let query = Query::from_str("SELECT id FROM t1 WHERE id = ?")?;
+        assert!(query.has_placeholders());
+        assert!(indexer
+            .index(query.clone(), OutOption::Value(&mut String::new()))
+            .is_err());
+        assert!(query.bind(&Params::new()).is_err());
+        assert!(query.bind(&Params::new().arg(1).arg(2)).is_err());
+
+        // Matcher of a predicate without placeholders is kept
+        let query = Query::build().table("t1").columns(vec!["id"]).predicate(
+            Predicate::build()
+                .column("address")
+                .operator(Operator::Like)
+                .matcher("^[BC]")?,
+        );
+        let bound = query.bind(&Params::new())?;
+        assert_eq!(
+            Some("^[BC]"),
+            bound
+                .predicates
+                .as_ref()
+                .and_then(|predicates| predicates[0].matcher.as_ref().map(|m| m.as_str()))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn prepared_query() -> CIndexResult<()> {
+        let mut indexer = sample_indexer()?;
+        indexer.add_table("t2", "id,address\n7,DD 1111\n2,EE 2222".as_bytes())?;
+
+        assert!(indexer.prepare("SELECT id FROM t1 WHERE age = 1").is_err());
+        assert!(indexer.prepare("SELECT age FROM t1").is_err());
+        assert!(indexer.prepare("SELECT id FROM t3").is_err());
+
+        let prepared = indexer.prepare("SELECT id,address FROM t1 WHERE id = ?")?;
+        for (id, expected) in [("1", "1,AA 1234\n"), ("3", "3,CC 8790\n")] {
+            let mut acc = String::new();
+            indexer.index_prepared(
+                &prepared,
+                &Params::new().arg(id),
+                OutOption::Value(&mut acc),
+            )?;
+            assert_eq!(expected, acc);
+        }
+
+        let mut acc = String::new();
+        indexer.index_prepared_on(
+            "t2",
+            &prepared,
+            &Params::new().arg(2),
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("2,EE 2222\n", acc);
+
+        // Columns are resolved again once the table's columns have changed
+        let mut execute =
+            |statement: &str| indexer.execute_raw(statement, OutOption::Value(&mut String::new()));
+        execute("ALTER TABLE t1 DROP COLUMN 'first name'")?;
+        let mut acc = String::new();
+        indexer.index_prepared(&prepared, &Params::new().arg(2), OutOption::Value(&mut acc))?;
+        assert_eq!("2,BB 4566\n", acc);
+        indexer.execute_raw(
+            "ALTER TABLE t1 RENAME COLUMN address TO place",
+            OutOption::Value(&mut String::new()),
+        )?;
+        assert!(indexer
+            .index_prepared(
+                &prepared,
+                &Params::new().arg(2),
+                OutOption::Value(&mut String::new())
+            )
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn explain_query() -> CIndexResult<()> {
+        let indexer = sample_indexer()?;
+
+        let query = Query::from_str(
+            "SELECT * FROM t1 WHERE id BETWEEN 1 AND 3 AND address LIKE ^A ORDER BY id DESC LIMIT 1",
+        )?;
+        let plan = indexer.explain(&query)?;
+        assert_eq!(3, plan.total_rows);
+        assert_eq!(2, plan.filters.len());
+        assert_eq!(AccessPath::Scan, plan.filters[0].access);
+        assert_eq!(Operator::Like, plan.filters[1].operator);
+        assert!(plan.sort.as_ref().map(|s| s.descending).unwrap_or(false));
+        assert_eq!(
+            vec!["id", "first name", "last name", "address"],
+            plan.projection
+        );
+        assert_eq!(Some(1), plan.limit);
+        assert_eq!(1, plan.estimated_rows);
+
+        let mut acc = String::new();
+        indexer.index_raw(
+            "EXPLAIN SELECT id FROM t1 WHERE id = 1",
+            OutOption::Value(&mut acc),
+        )?;
+        assert!(acc.starts_with("Table   \"t1\" (3 rows)"));
+        assert!(acc.contains("Filter  id = 1 [full scan]"));
+
+        assert!(indexer
+            .index_raw("EXPLAIN SELECT age FROM t1", OutOption::Value(&mut acc))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn query_round_trip() -> CIndexResult<()> {
+        let indexer = sample_indexer()?;
+
+        let built = Query::build()
+            .table("t1")
+            .columns(vec!["id", "first name"])
+            .predicate(Predicate::new("last name", Operator::In).args(vec!["doe", "it's, ok"]))
+            .predicate(Predicate::new("id", Operator::Between).args(vec!["1", "2"]))
+            .predicate(Predicate::new("address", Operator::NotEqual).args(vec!["AND"]));
+        let raw = built.to_string();
+        assert_eq!(
+            r#"SELECT 'id,first name' FROM t1 WHERE 'last name' IN (doe, 'it\'s, ok') AND id BETWEEN 1 AND 2 AND address != 'AND'"#,
+            raw
+        );
+
+        for raw in [
+            raw.as_str(),
+            "SELECT * FROM t1 WHERE address LIKE '^(AA|CC) ' ORDER BY id DESC OFFSET 1 LIMIT 1 HMAP ident,'first',last,addr FLAG PHD TP",
+            "SELECT id FROM t1 WHERE 'first name' = '' AND id IN (?, :id) AND address = '?'",
+        ] {
+            let query = Query::from_str(raw)?;
+            let reparsed = Query::from_str(&query.to_string())?;
+            assert_eq!(query.to_string(), reparsed.to_string());
+            if !query.has_placeholders() {
+                assert_eq!(
+                    indexer.index_get_records(query)?,
+                    indexer.index_get_records(reparsed)?
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn query_serde_round_trip() -> CIndexResult<()> {
+        let indexer = sample_indexer()?;
+
+        // Regex matcher is serialized as its pattern
+        let predicate = Predicate::build()
+            .column("address")
+            .operator(Operator::Like)
+            .matcher("^(AA|CC) ")?;
+        let json = serde_json::to_string(&predicate).expect("Failed to serialize a predicate");
+        let restored: Predicate =
+            serde_json::from_str(&json).expect("Failed to deserialize a predicate");
+        assert_eq!(predicate.to_string(), restored.to_string());
+        assert_eq!(
+            Some("^(AA|CC) "),
+            restored.matcher.as_ref().map(|m| m.as_str())
+        );
+
+        for raw in [
+            "SELECT * FROM t1 WHERE address LIKE '^(AA|CC) ' ORDER BY id DESC OFFSET 1 LIMIT 1 HMAP ident,'first',last,addr FLAG PHD TP",
+            "SELECT id FROM t1 WHERE 'last name' IN (doe, jojo) AND id BETWEEN 1 AND 3",
+        ] {
+            let query = Query::from_str(raw)?;
+            let json = serde_json::to_string(&query).expect("Failed to serialize a query");
+            let restored: Query =
+                serde_json::from_str(&json).expect("Failed to deserialize a query");
+            assert_eq!(query.to_string(), restored.to_string());
+            assert_eq!(
+                indexer.index_get_records(query)?,
+                indexer.index_get_records(restored)?
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn hash_index() -> CIndexResult<()> {
+        let mut indexer = sample_indexer()?;
+        let raw =
+            "SELECT id FROM t1 WHERE address != 'AA 1234' AND 'last name' IN (doe, jojo, doner)";
+        let mut expected = String::new();
+        indexer.index_raw(raw, OutOption::Value(&mut expected))?;
+        assert_eq!("2\n3\n", expected);
+
+        indexer.execute_raw("CREATE INDEX ON t1 ('last name')", OutOption::Term)?;
+        let plan = indexer.explain(&Query::from_str(raw)?)?;
+        assert_eq!(AccessPath::HashIndex, plan.filters[0].access);
+        assert_eq!("last name", plan.filters[0].column);
+        assert_eq!(3, plan.filters[0].estimated_rows);
+        assert_eq!(AccessPath::Filter, plan.filters[1].access);
+
+        let mut acc = String::new();
+        indexer.index_raw(raw, OutOption::Value(&mut acc))?;
+        assert_eq!(expected, acc);
+
+        assert!(indexer.create_index("t1", "age").is_err());
+        assert!(indexer
+            .execute_raw("CREATE INDEX t1 (id)", OutOption::Term)
+            .is_err());
+        assert!(indexer
+            .index_raw("CREATE INDEX ON t1 (id)", OutOption::Term)
+            .is_err());
+        assert!(indexer.drop_index("t1", "last name")?);
+        assert!(!indexer.drop_index("t1", "last name")?);
+        Ok(())
+    }
+
+    #[test]
+    fn ordered_index() -> CIndexResult<()> {
+        let mut indexer = sample_indexer()?;
+        let queries = [
+            "SELECT id FROM t1 WHERE address > 'AA 1234'",
+            "SELECT id FROM t1 WHERE address <= 'BB 4566' AND id != 1",
+            "SELECT id FROM t1 WHERE address BETWEEN 'AB' AND 'CD'",
+            "SELECT id FROM t1 WHERE address BETWEEN 'CD' AND 'AB'",
+            "SELECT id FROM t1 WHERE address IN ('CC 8790', 'AA 1234')",
+            "SELECT id FROM t1 ORDER BY address DESC LIMIT 2",
+            "SELECT id FROM t1 WHERE id != 2 ORDER BY address",
+        ];
+        let expected = queries
+            .iter()
+            .map(|raw| indexer.index_get_records(Query::from_str(raw)?))
+            .collect::<CIndexResult<Vec<_>>>()?;
+
+        indexer.execute_raw("CREATE INDEX ON t1 (address) USING BTREE", OutOption::Term)?;
+        for (raw, expected) in queries.iter().zip(expected) {
+            assert_eq!(expected, indexer.index_get_records(Query::from_str(raw)?)?);
+        }
+
+        let plan = indexer.explain(&Query::from_str(queries[2])?)?;
+        assert_eq!(AccessPath::OrderedIndex, plan.filters[0].access);
+        assert_eq!(2, plan.filters[0].estimated_rows);
+
+        let plan = indexer.explain(&Query::from_str(queries[5])?)?;
+        assert!(plan.sort.map(|s| s.indexed).unwrap_or(false));
+
+        assert!(indexer
+            .execute_raw("CREATE INDEX ON t1 (id) USING TREE", OutOption::Term)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn table_cache() -> CIndexResult<()> {
+        let dir = std::env::temp_dir();
+        let source = dir.join(format!("cindex_cache_source_{}.csv", std::process::id()));
+        let cache = dir.join(format!("cindex_cache_{}.bin", std::process::id()));
+        std::fs::write(&source, "id,name\n1,a\n2,b\n")?;
+
+        let mut indexer = sample_indexer()?;
+        indexer.add_table_from_path("t2", &source)?;
+        indexer.create_index_with_type("t2", "id", IndexType::Ordered)?;
+        indexer.create_index("t1", "address")?;
+        indexer.save_cache(&cache)?;
+
+        let mut loaded = Indexer::new();
+        loaded.load_cache(&cache)?;
+        for raw in [
+            "SELECT * FROM t1 WHERE address = 'BB 4566'",
+            "SELECT * FROM t2 WHERE id > 1",
+        ] {
+            assert_eq!(
+                indexer.index_get_records(Query::from_str(raw)?)?,
+                loaded.index_get_records(Query::from_str(raw)?)?
+            );
+            assert_ne!(
+                AccessPath::Scan,
+                loaded.explain(&Query::from_str(raw)?)?.filters[0].access
+            );
+        }
+
+        // Changed source invalidates cached table
+        std::fs::write(&source, "id,name\n1,a\n2,b\n3,c\n")?;
+        let mut loaded = Indexer::new();
+        loaded.load_cache(&cache)?;
+        let query = Query::from_str("SELECT name FROM t2 WHERE id > 1")?;
+        assert_eq!(
+            AccessPath::OrderedIndex,
+            loaded.explain(&query)?.filters[0].access
+        );
+        assert_eq!(
+            vec![vec!["b".to_owned()], vec!["c".to_owned()]],
+            loaded.index_get_records(query)?
+        );
+
+        // Failure of any table keeps existing tables intact
+        indexer.save_cache(&cache)?;
+        std::fs::write(&source, "id,name\n1,a,extra\n")?;
+        let mut loaded = Indexer::new();
+        loaded.add_table("t1", "id\n9".as_bytes())?;
+        assert!(loaded.load_cache(&cache).is_err());
+        assert!(!loaded.contains_table("t2"));
+        assert_eq!(
+            vec![vec!["9".to_owned()]],
+            loaded.index_get_records(Query::from_str("SELECT * FROM t1")?)?
+        );
+
+        std::fs::write(&cache, "invalid")?;
+        assert!(Indexer::new().load_cache(&cache).is_err());
+
+        // Unknown tag is rejected rather than read as a default
+        let mut bytes = b"CINDEX".to_vec();
+        bytes.extend(4u32.to_le_bytes());
+        bytes.extend(1u64.to_le_bytes());
+        bytes.extend(2u64.to_le_bytes());
+        bytes.extend(b"t3");
+        bytes.push(7);
+        std::fs::write(&cache, bytes)?;
+        assert!(matches!(
+            Indexer::new().load_cache(&cache),
+            Err(CIndexError::InvalidCache(_))
+        ));
+
+        std::fs::remove_file(source)?;
+        std::fs::remove_file(cache)?;
+        Ok(())
+    }
+
+    #[test]
+    fn edited_table_cache() -> CIndexResult<()> {
+        let dir = std::env::temp_dir();
+        let source = dir.join(format!("cindex_edited_source_{}.csv", std::process::id()));
+        let cache = dir.join(format!("cindex_edited_{}.bin", std::process::id()));
+        std::fs::write(&source, "id,name\n1,a\n2,b\n")?;
+
+        let mut indexer = Indexer::new();
+        indexer.add_table_from_path("t1", &source)?;
+        indexer.create_index("t1", "name")?;
+        let mut execute =
+            |statement: &str| indexer.execute_raw(statement, OutOption::Value(&mut String::new()));
+        execute("INSERT INTO t1 VALUES (3, c)")?;
+        execute("DELETE FROM t1 WHERE id = 1")?;
+        execute("ALTER TABLE t1 RENAME COLUMN name TO label")?;
+        indexer.save_cache(&cache)?;
+
+        // Edited table is kept even though its source has changed
+        std::fs::write(&source, "id,name\n9,z\n")?;
+        let mut loaded = Indexer::new();
+        loaded.load_cache(&cache)?;
+        let query = Query::from_str("SELECT id FROM t1 WHERE label = c")?;
+        assert_eq!(
+            AccessPath::HashIndex,
+            loaded.explain(&query)?.filters[0].access
+        );
+        assert_eq!(vec![vec!["3".to_owned()]], loaded.index_get_records(query)?);
+        assert_eq!(
+            vec![vec!["2".to_owned()], vec!["3".to_owned()]],
+            loaded.index_get_records(Query::from_str("SELECT id FROM t1")?)?
+        );
+
+        std::fs::remove_file(source)?;
+        std::fs::remove_file(cache)?;
+        Ok(())
+    }
+
+    #[test]
+    fn stream_query() -> CIndexResult<()> {
+        let indexer = sample_indexer()?;
+        let source = "id,first name,last name,address
+1,John,doe,AA 1234
+
+2,Janet,doner,\"BB
+4566\"
+3,Hevay,jojo,CC 8790";
+        let mut table_indexer = Indexer::new();
+        table_indexer.add_table("t1", source.as_bytes())?;
+
+        for raw in [
+            "SELECT * FROM t1",
+            "SELECT id,address FROM t1 WHERE id != 2 FLAG PHD",
+            "SELECT id,none FROM t1 ORDER BY id DESC OFFSET 1 LIMIT 1 FLAG SUP TP",
+            "SELECT id FROM t1 OFFSET 1",
+        ] {
+            let query = Query::from_str(raw)?;
+            let mut expected = String::new();
+            table_indexer.index(query.clone(), OutOption::Value(&mut expected))?;
+            let mut acc = String::new();
+            indexer.query_stream(source.as_bytes(), &query, OutOption::Value(&mut acc))?;
+            assert_eq!(expected.replace("\r\n", "\n"), acc);
+        }
+
+        // Reading stops after limit so that malformed trailing rows are never read
+        let mut acc = String::new();
+        indexer.query_stream(
+            "id,name\n1,a\n2,b\n3\n".as_bytes(),
+            &Query::from_str("SELECT name FROM stream WHERE id > 1 LIMIT 1")?,
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("b\n", acc);
+        assert!(indexer
+            .query_stream(
+                "id,name\n1,a\n2,b\n3\n".as_bytes(),
+                &Query::from_str("SELECT name FROM stream ORDER BY id LIMIT 1")?,
+                OutOption::Value(&mut acc),
+            )
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn columnar_storage() -> CIndexResult<()> {
+        let mut indexer = Indexer::new();
+        indexer.always_use_unix_newline(true);
+        indexer.add_table(
+            "t1",
+            "id,vendor\n1,acme\n2,globex\n3,acme\n4,acme\n5,globex".as_bytes(),
+        )?;
+
+        // Repeated texts are stored once
+        match &indexer.tables["t1"].storage {
+            Storage::Memory(data) => match &data.columns[1].cells {
+                Cells::Interned {
+                    dictionary,
+                    lookup,
+                    ids,
+                } => {
+                    assert_eq!(2, dictionary.len());
+                    assert_eq!(2, lookup.len());
+                    assert_eq!(vec![0, 1, 0, 0, 1], *ids);
+                }
+                Cells::Plain(_) => panic!("Text column should be interned"),
+            },
+            #[allow(unreachable_patterns)]
+            _ => panic!("Table should be stored in memory"),
+        }
+
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT id FROM t1 WHERE vendor = acme ORDER BY id DESC LIMIT 2",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("4\n3\n", acc);
+
+        // Compacted dictionary keeps finding existing texts
+        indexer.delete_where(
+            "t1",
+            vec![Predicate::build().column("vendor").args(vec!["globex"])],
+        )?;
+        indexer.insert_rows(
+            "t1",
+            &["id", "vendor"],
+            vec![
+                vec!["6".to_owned(), "acme".to_owned()],
+                vec!["7".to_owned(), "initech".to_owned()],
+            ],
+        )?;
+        match &indexer.tables["t1"].storage {
+            Storage::Memory(data) => match &data.columns[1].cells {
+                Cells::Interned {
+                    dictionary, ids, ..
+                } => {
+                    assert_eq!(2, dictionary.len());
+                    assert_eq!(vec![0, 0, 0, 0, 1], *ids);
+                }
+                Cells::Plain(_) => panic!("Text column should be interned"),
+            },
+            #[allow(unreachable_patterns)]
+            _ => panic!("Table should be stored in memory"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn resolved_columns() -> CIndexResult<()> {
+        let indexer = sample_indexer()?;
+
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT address,id,extra FROM t1 WHERE 'last name' LIKE ^do AND id != 1 ORDER BY 'first name' FLAG SUP",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("BB 4566,2,\n", acc);
+
+        let query = Query::build()
+            .table("t1")
+            .columns(vec!["id"])
+            .predicate(Predicate::new("age", Operator::Equal).args(vec!["1"]));
+        assert!(matches!(
+            indexer.index(query, OutOption::Value(&mut String::new())),
+            Err(CIndexError::InvalidColumn(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn limited_query() -> CIndexResult<()> {
+        let mut indexer = Indexer::new();
+        let mut table = String::from("id,group");
+        for id in 0..50 {
+            table.push_str(&format!("\n{},g{}", id, id % 7));
+        }
+        indexer.add_table("t1", table.as_bytes())?;
+
+        for order in ["", "ORDER BY group", "ORDER BY group DESC"] {
+            let full = indexer.index_get_records(Query::from_str(&format!(
+                "SELECT id FROM t1 WHERE id != 3 {}",
+                order
+            ))?)?;
+            assert_eq!(49, full.len());
+            for (offset, limit) in [
+                (0, 1),
+                (0, 10),
+                (5, 7),
+                (45, 10),
+                (60, 5),
+                // Huge range neither overflows nor allocates for it
+                (1_000_000_000_000_000_000, 1),
+                (1, usize::MAX),
+                (usize::MAX, usize::MAX),
+            ] {
+                let raw = format!(
+                    "SELECT id FROM t1 WHERE id != 3 {} OFFSET {} LIMIT {}",
+                    order, offset, limit
+                );
+                let limited = indexer.index_get_records(Query::from_str(&raw)?)?;
+                let start = offset.min(full.len());
+                let end = offset.saturating_add(limit).min(full.len());
+                assert_eq!(full[start..end], limited[..]);
+
+                // Stream keeps only rows within limit and ties keep their order
+                let mut streamed = String::new();
+                indexer.query_stream(
+                    table.as_bytes(),
+                    &Query::from_str(&raw)?,
+                    OutOption::Value(&mut streamed),
+                )?;
+                let expected = limited
+                    .iter()
+                    .map(|record| record.join(",") + "\n")
+                    .collect::<String>();
+                assert_eq!(expected, streamed.replace("\r\n", "\n"));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn predicate_selectivity() -> CIndexResult<()> {
+        let indexer = sample_indexer()?;
+
+        // Selective predicates come first and like comes last
+        let raw = "SELECT id FROM t1 WHERE address LIKE ^A AND id != 2 AND 'last name' = doe";
+        let plan = indexer.explain(&Query::from_str(raw)?)?;
+        assert_eq!(
+            vec![Operator::Equal, Operator::NotEqual, Operator::Like],
+            plan.filters.iter().map(|f| f.operator).collect::<Vec<_>>()
+        );
+        assert_eq!(1, plan.filters[0].estimated_rows);
+        assert_eq!(
+            vec![vec!["1".to_owned()]],
+            indexer.index_get_records(Query::from_str(raw)?)?
+        );
+
+        // Predicate out of column's range qualifies no row
+        for raw in [
+            "SELECT id FROM t1 WHERE id > 9",
+            "SELECT id FROM t1 WHERE id BETWEEN 3 AND 1",
+            "SELECT id FROM t1 WHERE address = 'AA 1234' AND 'last name' IN (a, z)",
+        ] {
+            let query = Query::from_str(raw)?;
+            assert_eq!(0, indexer.explain(&query)?.estimated_rows);
+            assert!(indexer.index_get_records(query)?.is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn insert_rows() -> CIndexResult<()> {
+        let mut indexer = sample_indexer()?;
+        indexer.create_index("t1", "address")?;
+
+        let mut acc = String::new();
+        assert_eq!(
+            2,
+            indexer.execute_raw(
+                "INSERT INTO t1 (id, address) VALUES (4, 'DD 1111'), (5,'EE, 2222')",
+                OutOption::Value(&mut acc),
+            )?
+        );
+        indexer.index_raw(
+            "SELECT * FROM t1 WHERE address = 'EE, 2222'",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("5,,,EE, 2222\n", acc);
+
+        assert_eq!(
+            1,
+            indexer.execute_raw(
+                "INSERT INTO t1 SELECT * FROM t1 WHERE id = 1",
+                OutOption::Value(&mut String::new()),
+            )?
+        );
+        assert_eq!(
+            1,
+            indexer.insert_rows(
+                "t1",
+                &[],
+                vec![vec![
+                    "6".to_owned(),
+                    "Jin".to_owned(),
+                    "kim".to_owned(),
+                    "FF 3333".to_owned()
+                ]],
+            )?
+        );
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT id FROM t1 WHERE id >= 1",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("1\n2\n3\n4\n5\n1\n6\n", acc);
+
+        // Invalid rows insert nothing
+        assert!(indexer
+            .insert_rows(
+                "t1",
+                &["id", "age"],
+                vec![vec!["7".to_owned(), "1".to_owned()]]
+            )
+            .is_err());
+        assert!(indexer
+            .insert_rows(
+                "t1",
+                &["id"],
+                vec![vec!["7".to_owned()], vec!["8".to_owned(), "x".to_owned()]]
+            )
+            .is_err());
+        assert!(indexer
+            .execute_raw(
+                "INSERT INTO t1 (id) 7",
+                OutOption::Value(&mut String::new())
+            )
+            .is_err());
+        assert!(indexer
+            .index_raw(
+                "INSERT INTO t1 VALUES (7)",
+                OutOption::Value(&mut String::new())
+            )
+            .is_err());
+        assert_eq!(
+            7,
+            indexer
+                .index_get_records(Query::from_str("SELECT id FROM t1")?)?
+                .len()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn update_rows() -> CIndexResult<()> {
+        let mut indexer = sample_indexer()?;
+        indexer.create_index("t1", "address")?;
+
+        assert_eq!(
+            2,
+            indexer.execute_raw(
+                "UPDATE t1 SET 'last name' = UPPER('first name'), address = 'ZZ, 0000' WHERE id >= 2",
+                OutOption::Value(&mut String::new()),
+            )?
+        );
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT id,'last name' FROM t1 WHERE address = 'ZZ, 0000'",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("2,JANET\n3,HEVAY\n", acc);
+
+        assert_eq!(
+            3,
+            indexer.execute_raw(
+                "UPDATE t1 SET address = lower(address)",
+                OutOption::Value(&mut String::new()),
+            )?
+        );
+        let mut acc = String::new();
+        indexer.index_raw("SELECT address FROM t1", OutOption::Value(&mut acc))?;
+        assert_eq!("aa 1234\nzz, 0000\nzz, 0000\n", acc);
+
+        // Invalid values update nothing
+        assert!(indexer
+            .execute_raw(
+                "UPDATE t1 SET address = 'x', age = 1 WHERE id = 1",
+                OutOption::Value(&mut String::new())
+            )
+            .is_err());
+        assert!(indexer
+            .execute_raw(
+                "UPDATE t1 SET address = SUM(id)",
+                OutOption::Value(&mut String::new())
+            )
+            .is_err());
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT address FROM t1 WHERE id = 1",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("aa 1234\n", acc);
+        Ok(())
+    }
+
+    #[test]
+    fn delete_rows() -> CIndexResult<()> {
+        let mut indexer = sample_indexer()?;
+        indexer.create_index("t1", "address")?;
+        indexer.create_index_with_type("t1", "id", IndexType::Ordered)?;
+
+        assert_eq!(
+            1,
+            indexer.execute_raw(
+                "DELETE FROM t1 WHERE address = 'BB 4566'",
+                OutOption::Value(&mut String::new()),
+            )?
+        );
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT id FROM t1 WHERE address IN ('AA 1234', 'CC 8790') ORDER BY id DESC",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("3\n1\n", acc);
+
+        assert_eq!(
+            1,
+            indexer.delete_where(
+                "t1",
+                vec![Predicate::build()
+                    .column("id")
+                    .operator(Operator::Bigger)
+                    .raw_args("2")],
+            )?
+        );
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT id FROM t1 WHERE id >= 1",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("1\n", acc);
+
+        assert_eq!(
+            0,
+            indexer.delete_where(
+                "t1",
+                vec![Predicate::build()
+                    .column("id")
+                    .operator(Operator::Equal)
+                    .raw_args("9")]
+            )?
+        );
+        assert!(indexer.delete_where("t2", vec![]).is_err());
+        assert_eq!(1, indexer.delete_where("t1", vec![])?);
+        assert!(indexer
+            .index_get_records(Query::from_str("SELECT id FROM t1")?)?
+            .is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn alter_table() -> CIndexResult<()> {
+        let mut indexer = sample_indexer()?;
+        indexer.create_index("t1", "address")?;
+        let mut execute =
+            |statement: &str| indexer.execute_raw(statement, OutOption::Value(&mut String::new()));
+
+        execute("ALTER TABLE t1 ADD COLUMN status DEFAULT 'open now'")?;
+        execute("ALTER TABLE t1 ADD note")?;
+        execute("ALTER TABLE t1 DROP COLUMN 'last name'")?;
+        execute("ALTER TABLE t1 RENAME COLUMN address TO location")?;
+        execute("ALTER TABLE t1 RENAME TO people")?;
+        assert!(execute("ALTER TABLE people ADD COLUMN status").is_err());
+        assert!(execute("ALTER TABLE people RENAME COLUMN id TO status").is_err());
+        assert!(execute("ALTER TABLE people DROP COLUMN address").is_err());
+        assert!(execute("ALTER TABLE t1 ADD COLUMN other").is_err());
+        assert!(execute("ALTER TABLE people MODIFY id").is_err());
+
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT * FROM people WHERE location = 'BB 4566' FLAG PHD",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!(
+            "id,first name,location,status,note\n2,Janet,BB 4566,open now,\n",
+            acc
+        );
+        assert!(indexer
+            .index_raw("SELECT * FROM t1", OutOption::Value(&mut String::new()))
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn materialize_query() -> CIndexResult<()> {
+        let mut indexer = sample_indexer()?;
+
+        assert_eq!(
+            0,
+            indexer.execute_raw(
+                "CREATE TABLE t2 AS SELECT id,address FROM t1 WHERE id >= 2 ORDER BY id DESC HMAP ident,addr",
+                OutOption::Value(&mut String::new()),
+            )?
+        );
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT * FROM t2 WHERE ident != 2 FLAG PHD",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("ident,addr\n3,CC 8790\n", acc);
+
+        indexer.materialize(
+            "t3",
+            &Query::from_str("SELECT id,extra FROM t1 WHERE id = 1 FLAG SUP")?,
+        )?;
+        let mut acc = String::new();
+        indexer.index_raw("SELECT * FROM t3 FLAG PHD", OutOption::Value(&mut acc))?;
+        assert_eq!("id,extra\n1,\n", acc);
+
+        assert!(indexer
+            .materialize("t3", &Query::from_str("SELECT * FROM t1")?)
+            .is_err());
+        assert!(indexer
+            .materialize("t4", &Query::from_str("SELECT id,id FROM t1")?)
+            .is_err());
+        assert!(indexer
+            .execute_raw(
+                "CREATE TABLE t4 SELECT * FROM t1",
+                OutOption::Value(&mut String::new())
+            )
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn save_table() -> CIndexResult<()> {
+        let mut indexer = sample_indexer()?;
+        indexer.execute_raw(
+            "UPDATE t1 SET address = 'A \"1\", 2' WHERE id = 1",
+            OutOption::Value(&mut String::new()),
+        )?;
+        indexer.execute_raw(
+            "DELETE FROM t1 WHERE id = 2",
+            OutOption::Value(&mut String::new()),
+        )?;
+
+        let mut saved = vec![];
+        indexer.save_table("t1", &mut saved, &WriterOption::new())?;
+        let saved = String::from_utf8(saved).unwrap();
+        assert_eq!(
+            "id,first name,last name,address\n1,John,doe,\"A \"\"1\"\", 2\"\n3,Hevay,jojo,CC 8790\n",
+            saved
+        );
+
+        // Saved table is read back as it was
+        indexer.add_table("t2", saved.as_bytes())?;
+        let mut acc = String::new();
+        indexer.index_raw("SELECT address FROM t2", OutOption::Value(&mut acc))?;
+        assert_eq!("A \"1\", 2\nCC 8790\n", acc);
+
+        let mut saved = vec![];
+        indexer.save_table(
+            "t1",
+            &mut saved,
+            &WriterOption::new()
+                .delimiter(';')
+                .quote_style(QuoteStyle::Always)
+                .newline("\r\n")
+                .header(false),
+        )?;
+        assert_eq!(
+            "\"1\";\"John\";\"doe\";\"A \"\"1\"\", 2\"\r\n\"3\";\"Hevay\";\"jojo\";\"CC 8790\"\r\n",
+            String::from_utf8(saved).unwrap()
+        );
+        assert!(indexer
+            .save_table("t3", &mut vec![], &WriterOption::new())
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn transaction() -> CIndexResult<()> {
+        let mut indexer = sample_indexer()?;
+        indexer.create_index("t1", "address")?;
+        let ids = |indexer: &Indexer, table: &str| -> CIndexResult<String> {
+            let mut acc = String::new();
+            indexer.index_raw(
+                &format!("SELECT id FROM {} WHERE address != 'x'", table),
+                OutOption::Value(&mut acc),
+            )?;
+            Ok(acc)
+        };
+
+        // Failed batch leaves nothing behind
+        assert!(indexer
+            .transaction(|tx| {
+                tx.execute_raw("DELETE FROM t1 WHERE id = 1", OutOption::Term)?;
+                tx.execute_raw("CREATE TABLE t2 AS SELECT * FROM t1", OutOption::Term)?;
+                tx.execute_raw("INSERT INTO t1 (id, age) VALUES (4, 10)", OutOption::Term)
+            })
+            .is_err());
+        assert_eq!("1\n2\n3\n", ids(&indexer, "t1")?);
+        assert!(!indexer.contains_table("t2"));
+
+        let mut execute =
+            |statement: &str| indexer.execute_raw(statement, OutOption::Value(&mut String::new()));
+        execute("BEGIN")?;
+        execute("UPDATE t1 SET address = 'AA 1234' WHERE id = 3")?;
+        execute("ALTER TABLE t1 RENAME TO t3")?;
+        execute("ROLLBACK")?;
+        execute("BEGIN TRANSACTION")?;
+        assert!(execute("BEGIN").is_err());
+        execute("DELETE FROM t1 WHERE address = 'AA 1234'")?;
+        execute("COMMIT")?;
+        assert!(execute("COMMIT").is_err());
+        assert!(execute("ROLLBACK").is_err());
+
+        assert!(!indexer.contains_table("t3"));
+        assert_eq!("2\n3\n", ids(&indexer, "t1")?);
+        Ok(())
+    }
+
+    #[test]
+    fn undo_history() -> CIndexResult<()> {
+        let mut indexer = sample_indexer()?;
+        let addresses = |indexer: &Indexer| -> CIndexResult<String> {
+            let mut acc = String::new();
+            indexer.index_raw("SELECT address FROM t1", OutOption::Value(&mut acc))?;
+            Ok(acc)
+        };
+
+        // History is disabled by default
+        indexer.delete_where("t1", vec![])?;
+        assert!(!indexer.undo("t1")?);
+        indexer.add_table("t1", "id,address\n1,AA\n2,BB".as_bytes())?;
+
+        indexer.set_history_limit(2);
+        let mut execute =
+            |statement: &str| indexer.execute_raw(statement, OutOption::Value(&mut String::new()));
+        // Empty history restores nothing
+        assert_eq!(0, execute("UNDO t1")?);
+        assert_eq!(0, execute("REDO t1")?);
+        execute("UPDATE t1 SET address = 'CC' WHERE id = 1")?;
+        execute("DELETE FROM t1 WHERE id = 2")?;
+        execute("UPDATE t1 SET address = LOWER(address)")?;
+        assert!(execute("UPDATE t1 SET age = 1").is_err());
+        assert_eq!(1, execute("UNDO t1")?);
+        // Modification without an affected row is not recorded and keeps redo
+        execute("DELETE FROM t1 WHERE id = 9")?;
+        execute("UPDATE t1 SET address = 'DD' WHERE id = 9")?;
+        assert_eq!("CC\n", addresses(&indexer)?);
+        assert!(indexer.undo("t1")?);
+        assert_eq!("CC\nBB\n", addresses(&indexer)?);
+        // Oldest modification is out of the limit
+        assert!(!indexer.undo("t1")?);
+        assert!(indexer.redo("t1")?);
+        assert_eq!("CC\n", addresses(&indexer)?);
+
+        // New modification clears redo
+        indexer.insert_rows("t1", &["id"], vec![vec!["3".to_owned()]])?;
+        assert!(!indexer.redo("t1")?);
+        indexer.rename_table("t1", "t2")?;
+        assert!(indexer.undo("t2")?);
+        assert!(indexer
+            .execute_raw("REDO t1", OutOption::Value(&mut String::new()))
+            .is_err());
+        let mut acc = String::new();
+        indexer.index_raw("SELECT id FROM t2", OutOption::Value(&mut acc))?;
+        assert_eq!("1\n", acc);
+        assert!(!indexer.drop_index("t2", "id")?);
+        assert!(indexer.redo("t2")?);
+        Ok(())
+    }
+
+    #[test]
+    fn table_constraints() -> CIndexResult<()> {
+        let mut indexer = Indexer::new();
+        indexer.always_use_unix_newline(true);
+        let constraints = || -> CIndexResult<Vec<Constraint>> {
+            Ok(vec![
+                Constraint::not_null("name"),
+                Constraint::unique(vec!["id"]),
+                Constraint::check("status IN (open, closed) AND id != 0")?,
+            ])
+        };
+
+        // Every violation of a feed is reported with its row
+        let err = indexer
+            .add_table_with_constraints(
+                "t1",
+                "id,name,status\n1,a,open\n1,,open\n0,c,\n3,d,lost".as_bytes(),
+                constraints()?,
+            )
+            .unwrap_err();
+        match err {
+            CIndexError::ConstraintViolation(violations) => assert_eq!(
+                vec![
+                    "Row 1 violates NOT NULL (name)",
+                    "Row 1 violates UNIQUE (id)",
+                    "Row 2 violates CHECK (status IN (open, closed) AND id != 0)",
+                    "Row 3 violates CHECK (status IN (open, closed) AND id != 0)",
+                ],
+                violations.iter().map(|v| v.to_string()).collect::<Vec<_>>()
+            ),
+            _ => panic!("Unexpected error {}", err),
+        }
+        assert!(!indexer.contains_table("t1"));
+
+        indexer.add_table_with_constraints(
+            "t1",
+            "id,name,status\n1,a,open\n2,b,\n".as_bytes(),
+            constraints()?,
+        )?;
+        let mut execute =
+            |statement: &str| indexer.execute_raw(statement, OutOption::Value(&mut String::new()));
+        assert!(execute("INSERT INTO t1 VALUES (3, c, open), (2, d, closed)").is_err());
+        assert!(execute("UPDATE t1 SET status = lost WHERE id = 2").is_err());
+        assert!(execute("DELETE FROM t1 WHERE id = 1").is_ok());
+        assert!(execute("ALTER TABLE t1 DROP COLUMN name").is_err());
+        execute("ALTER TABLE t1 RENAME COLUMN name TO label")?;
+        assert!(execute("UPDATE t1 SET label = ''").is_err());
+
+        // Changed rows are checked against keys of other rows and of each other
+        match execute("INSERT INTO t1 VALUES (3, c, open), (2, d, closed)").unwrap_err() {
+            CIndexError::ConstraintViolation(violations) => assert_eq!(
+                vec!["Row 2 violates UNIQUE (id)"],
+                violations.iter().map(|v| v.to_string()).collect::<Vec<_>>()
+            ),
+            err => panic!("Unexpected error {}", err),
+        }
+        execute("INSERT INTO t1 VALUES (3, c, open)")?;
+        assert!(execute("UPDATE t1 SET id = 4").is_err());
+        assert!(execute("UPDATE t1 SET id = 2 WHERE id = 3").is_err());
+        execute("DELETE FROM t1 WHERE id = 2")?;
+        execute("UPDATE t1 SET id = 2 WHERE id = 3")?;
+        execute("INSERT INTO t1 VALUES (3, e, open)")?;
+        assert!(execute("INSERT INTO t1 VALUES (2, f, open)").is_err());
+
+        let mut acc = String::new();
+        indexer.index_raw("SELECT * FROM t1", OutOption::Value(&mut acc))?;
+        assert_eq!("2,c,open\n3,e,open\n", acc);
+
+        // Constraints are kept in a cache
+        let cache =
+            std::env::temp_dir().join(format!("cindex_constraint_{}.bin", std::process::id()));
+        indexer.save_cache(&cache)?;
+        let mut loaded = Indexer::new();
+        loaded.load_cache(&cache)?;
+        std::fs::remove_file(&cache)?;
+        assert!(loaded
+            .insert_rows(
+                "t1",
+                &["id", "label"],
+                vec![vec!["2".to_owned(), "x".to_owned()]]
+            )
+            .is_err());
+
+        assert!(indexer
+            .set_constraints("t1", vec![Constraint::check("id = 1")?])
+            .is_err());
+        indexer.set_constraints("t1", vec![])?;
+        indexer.insert_rows("t1", &["id"], vec![vec!["2".to_owned()]])?;
+
+        // Empty values are never duplicates of each other
+        indexer.add_table_with_constraints(
+            "t2",
+            "id,code\n1,\n2,".as_bytes(),
+            vec![Constraint::unique(vec!["code"])],
+        )?;
+        let mut execute =
+            |statement: &str| indexer.execute_raw(statement, OutOption::Value(&mut String::new()));
+        execute("INSERT INTO t2 VALUES (3, ''), (4, ''), (5, x)")?;
+        execute("UPDATE t2 SET code = '' WHERE id = 5")?;
+        execute("DELETE FROM t2 WHERE id = 1")?;
+        execute("UPDATE t2 SET code = y WHERE id = 2")?;
+        assert!(execute("INSERT INTO t2 VALUES (6, y)").is_err());
+        let mut acc = String::new();
+        indexer.index_raw("SELECT id,code FROM t2", OutOption::Value(&mut acc))?;
+        assert_eq!("2,y\n3,\n4,\n5,\n", acc);
+        Ok(())
+    }
+
+    #[test]
+    fn primary_key() -> CIndexResult<()> {
+        let mut indexer = Indexer::new();
+        indexer.always_use_unix_newline(true);
+        indexer.add_table(
+            "t1",
+            "id,name,status\n1,a,open\n2,b,open\n2,c,closed".as_bytes(),
+        )?;
+
+        // Key is not declared over duplicates
+        assert!(indexer.set_primary_key("t1", &["id"]).is_err());
+        assert!(indexer.get_by_key("t1", &["1"]).is_err());
+        indexer.set_primary_key("t1", &["id", "name"])?;
+        let status = |row: Option<crate::Row>| row.map(|r| r.values["status"].to_string());
+        assert_eq!(
+            Some("closed".to_owned()),
+            status(indexer.get_by_key("t1", &["2", "c"])?)
+        );
+        assert_eq!(None, status(indexer.get_by_key("t1", &["2", "d"])?));
+        assert!(indexer.get_by_key("t1", &["2"]).is_err());
+
+        indexer.delete_where(
+            "t1",
+            vec![Predicate::build().column("name").args(vec!["c"])],
+        )?;
+        indexer.set_primary_key("t1", &["id"])?;
+        assert_eq!(Some("a".to_owned()), {
+            let row = indexer.get_by_key("t1", &["1"])?;
+            row.map(|r| r.values["name"].to_string())
+        });
+
+        let mut execute =
+            |statement: &str| indexer.execute_raw(statement, OutOption::Value(&mut String::new()));
+        assert!(execute("INSERT INTO t1 VALUES (1, x, open)").is_err());
+        assert!(execute("INSERT INTO t1 (name) VALUES (x)").is_err());
+        execute("INSERT INTO t1 VALUES (1, x, closed), (3, c, open) ON CONFLICT DO NOTHING")?;
+        execute("INSERT INTO t1 (id, status) VALUES (2, closed), (4, open) on conflict do update")?;
+        execute("UPSERT INTO t1 (id, name) VALUES (4, d), (4, e)")?;
+        assert!(execute("INSERT INTO t1 VALUES (5, e, open) ON CONFLICT DO REPLACE").is_err());
+        assert!(execute("UPSERT INTO t1 VALUES (5, e, open) ON CONFLICT DO NOTHING").is_err());
+        assert!(execute("UPSERT INTO t1 (name) VALUES (f)").is_err());
+
+        let mut acc = String::new();
+        indexer.index_raw("SELECT * FROM t1 ORDER BY id", OutOption::Value(&mut acc))?;
+        assert_eq!("1,a,open\n2,b,closed\n3,c,open\n4,e,open\n", acc);
+        assert_eq!(
+            2,
+            indexer.upsert_rows(
+                "t1",
+                &["id", "status"],
+                vec![
+                    vec!["3".to_owned(), "closed".to_owned()],
+                    vec!["5".to_owned(), "open".to_owned()],
+                ]
+            )?
+        );
+        let status = indexer
+            .get_by_key("t1", &["3"])?
+            .map(|r| r.values["status"].to_string());
+        assert_eq!(Some("closed".to_owned()), status);
+        assert!(indexer.get_by_key("t1", &["5"])?.is_some());
+
+        // Key columns cannot be dropped and follow a renamed column
+        let mut execute =
+            |statement: &str| indexer.execute_raw(statement, OutOption::Value(&mut String::new()));
+        assert!(execute("ALTER TABLE t1 DROP COLUMN id").is_err());
+        execute("ALTER TABLE t1 RENAME COLUMN id TO key")?;
+        assert!(execute("UPDATE t1 SET key = 1 WHERE key = 2").is_err());
+        execute("UNDO t1")?;
+        execute("REDO t1")?;
+
+        // Key follows deleted and updated rows, and a failed insert writes no row
+        execute("DELETE FROM t1 WHERE key = 1")?;
+        execute("UPDATE t1 SET key = 6 WHERE key = 2")?;
+        execute("INSERT INTO t1 VALUES (2, g, open)")?;
+        assert!(execute("INSERT INTO t1 VALUES (7, h, open), (6, i, open)").is_err());
+        let name = |row: Option<crate::Row>| row.map(|r| r.values["name"].to_string());
+        assert_eq!(
+            Some("c".to_owned()),
+            name(indexer.get_by_key("t1", &["3"])?)
+        );
+        assert_eq!(
+            Some("b".to_owned()),
+            name(indexer.get_by_key("t1", &["6"])?)
+        );
+        assert_eq!(
+            Some("g".to_owned()),
+            name(indexer.get_by_key("t1", &["2"])?)
+        );
+        assert_eq!(None, name(indexer.get_by_key("t1", &["1"])?));
+        assert_eq!(None, name(indexer.get_by_key("t1", &["7"])?));
+
+        // Key is kept in a cache
+        let cache = std::env::temp_dir().join(format!("cindex_key_{}.bin", std::process::id()));
+        indexer.save_cache(&cache)?;
+        let mut loaded = Indexer::new();
+        loaded.load_cache(&cache)?;
+        std::fs::remove_file(&cache)?;
+        assert!(loaded.get_by_key("t1", &["4"])?.is_some());
+        assert!(loaded
+            .insert_rows("t1", &["key"], vec![vec!["4".to_owned()]])
+            .is_err());
+
+        indexer.set_primary_key("t1", &[])?;
+        assert!(indexer.get_by_key("t1", &["4"]).is_err());
+        indexer.insert_rows("t1", &["key"], vec![vec!["4".to_owned()]])?;
+        Ok(())
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn thread_pool() -> CIndexResult<()> {
+        let mut indexer = Indexer::new();
+        indexer.always_use_unix_newline(true);
+        indexer.set_thread_count(2)?;
+        let mut table = String::from("id,group");
+        for id in 0..100 {
+            table.push_str(&format!("\n{},g{}", id, id % 3));
+        }
+        indexer.add_table("t1", table.as_bytes())?;
+
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT id FROM t1 WHERE group = g1 ORDER BY group DESC FLAG TP",
+            OutOption::Value(&mut acc),
+        )?;
+        let expected = (0..100)
+            .filter(|id| id % 3 == 1)
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        assert_eq!(expected + "\n", acc);
+
+        // Transpose of no record is empty
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT id FROM t1 WHERE group = g5 FLAG TP",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("", acc);
+        Ok(())
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mapped_table() -> CIndexResult<()> {
+        let source = std::env::temp_dir().join(format!("cindex_mapped_{}.csv", std::process::id()));
+        std::fs::write(
+            &source,
+            "id,first name,last name,address\r\n1,John,doe,AA 1234\r\n\r\n2,Janet,doner,\"BB\n4566\"\r\n3,Hevay,jojo,\"CC, 8790\"",
+        )?;
+
+        let mut indexer = Indexer::new();
+        indexer.always_use_unix_newline(true);
+        indexer.add_table_mapped("t1", &source)?;
+        let records = indexer.index_get_records(Query::from_str(
+            "SELECT id,address FROM t1 WHERE id >= 2 ORDER BY id DESC",
+        )?)?;
+        assert_eq!(
+            vec![
+                vec!["3".to_owned(), "CC, 8790".to_owned()],
+                vec!["2".to_owned(), "BB\n4566".to_owned()]
+            ],
+            records
+        );
+
+        indexer.create_index("t1", "last name")?;
+        let mut acc = String::new();
+        indexer.index_raw(
+            "SELECT 'first name' FROM t1 WHERE 'last name' IN (doe, jojo)",
+            OutOption::Value(&mut acc),
+        )?;
+        assert_eq!("John\nHevay\n", acc);
+
+        // Cached mapped table is mapped from its source again
+        let cache = std::env::temp_dir().join(format!("cindex_mapped_{}.bin", std::process::id()));
+        indexer.save_cache(&cache)?;
+        let mut loaded = Indexer::new();
+        loaded.load_cache(&cache)?;
+        let query = Query::from_str("SELECT id FROM t1 WHERE 'last name' = jojo")?;
+        assert_eq!(
+            AccessPath::HashIndex,
+            loaded.explain(&query)?.filters[0].access
+        );
+        assert_eq!(vec![vec!["3".to_owned()]], loaded.index_get_records(query)?);
+        std::fs::remove_file(cache)?;
+
+        std::fs::write(&source, "id,name\n1,a,b\n")?;
+        assert!(indexer.add_table_mapped("t2", &source).is_err());
+
+        std::fs::remove_file(source)?;
+        Ok(())
+    }
 }
@@ -0,0 +1,125 @@
+//! Csv output of a table
+
+use crate::table::Table;
+use crate::CIndexResult;
+use std::borrow::Cow;
+use std::io::Write;
+
+/// When to quote a field of csv output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStyle {
+    /// Quote a field only if it contains a delimiter, quote or newline
+    Necessary,
+    Always,
+    /// Never quote a field even if csv output cannot be read back
+    Never,
+}
+
+/// Options to write a table as csv
+#[derive(Debug, Clone)]
+pub struct WriterOption {
+    pub(crate) delimiter: char,
+    pub(crate) quote_style: QuoteStyle,
+    pub(crate) newline: Option<String>,
+    pub(crate) header: bool,
+}
+
+impl Default for WriterOption {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WriterOption {
+    /// Create an option of comma delimiter with header
+    ///
+    /// Newline follows an indexer's newline option unless set.
+    pub fn new() -> Self {
+        Self {
+            delimiter: ',',
+            quote_style: QuoteStyle::Necessary,
+            newline: None,
+            header: true,
+        }
+    }
+
+    /// Set a delimiter of fields
+    pub fn delimiter(mut self, delimiter: char) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Set when to quote a field
+    pub fn quote_style(mut self, quote_style: QuoteStyle) -> Self {
+        self.quote_style = quote_style;
+        self
+    }
+
+    /// Set a newline of every row
+    pub fn newline(mut self, newline: &str) -> Self {
+        self.newline.replace(newline.to_owned());
+        self
+    }
+
+    /// Set whether to write a header
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Quote a field with doubled quotes inside
+    fn quote<'a>(&self, field: &'a str) -> Cow<'a, str> {
+        let requires_quote = match self.quote_style {
+            QuoteStyle::Always => true,
+            QuoteStyle::Never => false,
+            QuoteStyle::Necessary => field
+                .chars()
+                .any(|ch| ch == self.delimiter || matches!(ch, '"' | '\r' | '\n')),
+        };
+        if requires_quote {
+            Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+        } else {
+            Cow::Borrowed(field)
+        }
+    }
+
+    /// Write a single line of fields
+    fn write_fields<'a>(
+        &self,
+        writer: &mut impl Write,
+        fields: impl Iterator<Item = &'a str>,
+        newline: &str,
+    ) -> CIndexResult<()> {
+        let mut line = String::new();
+        for (index, field) in fields.enumerate() {
+            if index > 0 {
+                line.push(self.delimiter);
+            }
+            line.push_str(&self.quote(field));
+        }
+        line.push_str(newline);
+        writer.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Write every row of a table in order of columns
+pub(crate) fn write_table(
+    table: &Table,
+    writer: &mut impl Write,
+    option: &WriterOption,
+    newline: &str,
+) -> CIndexResult<()> {
+    let newline = option.newline.as_deref().unwrap_or(newline);
+    let columns = table.column_names();
+    if option.header {
+        option.write_fields(writer, columns.iter().copied(), newline)?;
+    }
+    let positions = (0..columns.len()).map(Some).collect::<Vec<_>>();
+    for position in 0..table.row_count() {
+        let values = table.record(position).project(&positions);
+        option.write_fields(writer, values.iter().map(|v| v.as_str()), newline)?;
+    }
+    writer.flush()?;
+    Ok(())
+}